@@ -0,0 +1,163 @@
+//! Times the day 8 solution on a generated chart much bigger than the puzzle
+//! input, next to the original string keyed version, and counts what each
+//! walk allocates. Run with `cargo bench`.
+#[allow(dead_code)]
+#[path = "../src/bin/day08.rs"]
+mod day08;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+const RUNS: u32 = 10;
+
+/// The system allocator, counting every allocation it makes
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Counting = Counting;
+
+/// The chart as day 8 first parsed it, every node a `String` looked up in a
+/// `HashMap` on every step
+mod baseline {
+    use super::HashMap;
+
+    pub struct Chart {
+        turns: String,
+        connections: HashMap<String, Turn>,
+    }
+
+    struct Turn {
+        left: String,
+        right: String,
+    }
+
+    impl From<&str> for Chart {
+        fn from(item: &str) -> Self {
+            let (turns, connections) = item.split_once("\n\n").unwrap();
+            let turns = turns.trim().to_owned();
+            let connections: HashMap<String, Turn> = connections
+                .lines()
+                .map(|line| {
+                    let (key, values) = line.split_once(" = ").unwrap();
+                    let key = key.trim().to_owned();
+                    let (lval, rval) = values.split_once(", ").unwrap();
+                    (
+                        key,
+                        Turn {
+                            left: lval.replace('(', "").trim().to_owned(),
+                            right: rval.replace(')', "").trim().to_owned(),
+                        },
+                    )
+                })
+                .collect();
+            Self { turns, connections }
+        }
+    }
+
+    impl Chart {
+        pub fn walk(&self) -> u64 {
+            let mut key = "AAA".to_string();
+            let mut steps = 0;
+            for (step, direction) in self.turns.chars().cycle().enumerate() {
+                key = match direction {
+                    'L' => self.connections.get(&key).unwrap().left.to_owned(),
+                    'R' => self.connections.get(&key).unwrap().right.to_owned(),
+                    _ => panic!("Unknown direction"),
+                };
+                if key == "ZZZ" {
+                    steps = step + 1;
+                    break;
+                }
+            }
+            steps as u64
+        }
+    }
+}
+
+/// A chart shaped like the puzzle input: each ghost runs down its own pair of
+/// tracks, with the turns only picking the track, and loops back to the start
+/// of them after the `--Z` nodes. The loop lengths are the turn count times
+/// a distinct prime, so the ghosts line up after `turns * product(primes)`.
+fn make_chart(turns: usize, primes: &[usize]) -> (String, u64) {
+    let directions: String = (0..turns)
+        .map(|i| if i * 7 % 5 < 2 { 'L' } else { 'R' })
+        .collect();
+    let mut lines = vec![];
+    for (ghost, &prime) in primes.iter().enumerate() {
+        let length = turns * prime;
+        let name = |pos: usize, track: usize| match (ghost, pos, track) {
+            (0, 0, _) => "AAA".to_string(),
+            (0, _, 0) if pos == length => "ZZZ".to_string(),
+            (_, 0, _) => format!("G{ghost}A"),
+            _ if pos == length => format!("G{ghost}T{track}Z"),
+            _ => format!("G{ghost}T{track}N{pos}"),
+        };
+        for pos in 0..=length {
+            let next = if pos == length { 1 } else { pos + 1 };
+            for track in 0..2 {
+                // both tracks share the start node
+                if pos == 0 && track == 1 {
+                    continue;
+                }
+                let (left, right) = (name(next, 0), name(next, 1));
+                lines.push(format!("{} = ({left}, {right})", name(pos, track)));
+            }
+        }
+    }
+    let steps = primes.iter().fold(turns as u64, |acc, &p| acc * p as u64);
+    (format!("{directions}\n\n{}", lines.join("\n")), steps)
+}
+
+fn time<T>(label: &str, mut f: impl FnMut() -> T) {
+    let now = Instant::now();
+    for _ in 0..RUNS {
+        black_box(f());
+    }
+    println!("{label}: {:?}", now.elapsed() / RUNS);
+}
+
+/// How many allocations a single call of `f` makes
+fn allocations<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn main() {
+    let (chart, steps) = make_chart(31, &[11, 13, 17, 19, 23, 29]);
+    assert_eq!(day08::earliest_arrival(&chart), Some(steps));
+    println!("Chart has {} nodes", chart.lines().count() - 2);
+
+    let (old, new) = (
+        baseline::Chart::from(chart.as_str()),
+        day08::Chart::from(chart.as_str()),
+    );
+    assert_eq!(old.walk(), new.walk());
+    let (old_allocs, new_allocs) = (allocations(|| old.walk()), allocations(|| new.walk()));
+    println!("Part 1 walk allocations: {old_allocs} string keyed, {new_allocs} interned");
+    assert_eq!(new_allocs, 0);
+
+    time("Parse, string keyed", || {
+        baseline::Chart::from(chart.as_str())
+    });
+    time("Parse, interned", || day08::Chart::from(chart.as_str()));
+    time("Part 1 walk, string keyed", || old.walk());
+    time("Part 1 walk, interned", || new.walk());
+    time("Part 2", || day08::earliest_arrival(&chart));
+}
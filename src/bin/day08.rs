@@ -1,101 +1,237 @@
 //! Day 8: Haunted Wasteland
-use num::integer::lcm;
-use std::collections::HashMap;
+use aoc_2023::dot;
+use aoc_2023::graph::LabeledGraph;
+use aoc_2023::number_theory::{crt, CrtError};
 use std::convert::From;
 use std::env;
 use std::fs::read_to_string;
 
-struct Chart {
-    turns: String,
-    connections: HashMap<String, Turn>,
-}
-
-struct Turn {
-    left: String,
-    right: String,
+/// The left/right turn directions as indices into each node's
+/// `[left, right]` neighbors, the interned node graph, and which nodes are
+/// `--A` starts and `--Z` ends
+pub struct Chart {
+    turns: Vec<usize>,
+    graph: LabeledGraph<[u32; 2]>,
+    is_start: Vec<bool>,
+    is_end: Vec<bool>,
 }
 
 impl From<&str> for Chart {
     fn from(item: &str) -> Self {
-        let (turns, connections) = item.split_once("\n\n").unwrap();
-        let turns = turns.trim().to_owned();
-        let connections: HashMap<String, Turn> = connections
-            .lines()
-            .map(|line| {
-                let (key, values) = line.split_once(" = ").unwrap();
-                let key = key.trim().to_owned();
-                let (lval, rval) = values.split_once(", ").unwrap();
-                (
-                    key,
-                    Turn {
-                        left: lval.replace('(', "").trim().to_owned(),
-                        right: rval.replace(')', "").trim().to_owned(),
-                    },
-                )
+        match Chart::new(item) {
+            Ok(chart) => chart,
+            Err(err) => panic!("Bad chart: {err}"),
+        }
+    }
+}
+
+impl Chart {
+    /// Parses a chart, which has to define every node it refers to
+    pub fn new(data: &str) -> Result<Self, String> {
+        let (turns, connections) = data
+            .split_once("\n\n")
+            .ok_or("Missing blank line after the turns")?;
+        let turns = turns
+            .trim()
+            .chars()
+            .map(|direction| match direction {
+                'L' => Ok(0),
+                'R' => Ok(1),
+                _ => Err(format!("Unknown direction {direction:?}")),
             })
-            .collect();
-        Self { turns, connections }
+            .collect::<Result<_, _>>()?;
+        let mut graph = LabeledGraph::new();
+        let mut defined = vec![];
+        for line in connections.lines() {
+            let (key, values) = line.split_once(" = ").ok_or(format!("Bad line {line:?}"))?;
+            let key = graph.intern(key.trim());
+            let (lval, rval) = values
+                .split_once(", ")
+                .ok_or(format!("Bad line {line:?}"))?;
+            let left = graph.intern(lval.replace('(', "").trim());
+            let right = graph.intern(rval.replace(')', "").trim());
+            *graph.neighbors_mut(key) = [left, right];
+            defined.resize(graph.len(), false);
+            defined[key as usize] = true;
+        }
+        defined.resize(graph.len(), false);
+        if let Some(id) = graph.ids().find(|&id| !defined[id as usize]) {
+            return Err(format!("Node {:?} is never defined", graph.label(id)));
+        }
+        let ends_with = |end| {
+            graph
+                .ids()
+                .map(|id| graph.label(id).ends_with(end))
+                .collect()
+        };
+        let (is_start, is_end) = (ends_with('A'), ends_with('Z'));
+        Ok(Self {
+            turns,
+            graph,
+            is_start,
+            is_end,
+        })
+    }
+
+    /// Steps from `AAA` to `ZZZ`, on interned ids so nothing is allocated
+    /// along the way
+    pub fn walk(&self) -> u64 {
+        let Chart { turns, graph, .. } = self;
+        let mut node = graph.id("AAA").unwrap();
+        let end = graph.id("ZZZ").unwrap();
+        let mut steps = 0;
+        for (step, &turn) in turns.iter().cycle().enumerate() {
+            node = graph.neighbors(node)[turn];
+            if node == end {
+                steps = step + 1;
+                break;
+            }
+        }
+        steps as u64
+    }
+
+    /// Graphviz DOT of the node graph, with the `--A` start nodes
+    /// and `--Z` end nodes highlighted
+    pub fn to_dot(&self) -> dot::Graph {
+        let mut graph = dot::Graph::directed("chart");
+        let mut keys: Vec<&str> = self.graph.ids().map(|id| self.graph.label(id)).collect();
+        keys.sort();
+        for key in keys.iter() {
+            if key.ends_with('A') {
+                graph.node(key, &[("style", "filled"), ("fillcolor", "palegreen")]);
+            } else if key.ends_with('Z') {
+                graph.node(key, &[("style", "filled"), ("fillcolor", "tomato")]);
+            }
+        }
+        for key in keys {
+            let [left, right] = self.graph.neighbors(self.graph.id(key).unwrap());
+            let (left, right) = (self.graph.label(*left), self.graph.label(*right));
+            if left == right {
+                graph.edge(key, left, &[("label", "LR")]);
+            } else {
+                graph.edge(key, left, &[("label", "L")]);
+                graph.edge(key, right, &[("label", "R")]);
+            }
+        }
+        graph
     }
 }
 
 /// Starting from Node AAA traverse the graph to Node ZZZ following
 /// the given turn directions and report number of steps
-fn day08_p1(chart: &str) -> u64 {
-    // I think I need to destructure this struct so I can borrow independently?
-    let Chart { turns, connections } = Chart::from(chart);
-    let mut key = "AAA".to_string();
-    let mut steps = 0;
-    for (step, direction) in turns.chars().cycle().enumerate() {
-        key = match direction {
-            'L' => connections.get(&key).unwrap().left.to_owned(),
-            'R' => connections.get(&key).unwrap().right.to_owned(),
-            _ => panic!("Unknown direction"),
-        };
-        if key == "ZZZ" {
-            steps = step + 1;
-            break;
+pub fn day08_p1(chart: &str) -> u64 {
+    Chart::from(chart).walk()
+}
+
+/// How a single ghost moves through the chart forever. Since the ghost's
+/// next move only depends on its node and where it is in the turn
+/// directions, it has to eventually repeat one of those states and loop.
+#[derive(Debug, PartialEq)]
+struct GhostCycle {
+    /// step at which the ghost first enters its loop
+    pre_period: u64,
+    cycle_len: u64,
+    /// steps before the loop on which the ghost is on a --Z node
+    hits_before: Vec<u64>,
+    /// steps within the first trip around the loop on which the ghost is on
+    /// a --Z node, these repeat every `cycle_len` steps
+    cycle_hits: Vec<u64>,
+}
+
+impl GhostCycle {
+    fn trace(chart: &Chart, start: u32) -> Self {
+        let turns = &chart.turns;
+        // first step each (node, turn index) state was seen on
+        let mut seen = vec![u64::MAX; chart.graph.len() * turns.len()];
+        let mut hits = vec![];
+        let mut node = start;
+        let mut step = 0;
+        loop {
+            let turn_idx = step as usize % turns.len();
+            let state = node as usize * turns.len() + turn_idx;
+            if seen[state] != u64::MAX {
+                let pre_period = seen[state];
+                let (hits_before, cycle_hits) = hits.iter().partition(|&&hit| hit < pre_period);
+                return Self {
+                    pre_period,
+                    cycle_len: step - pre_period,
+                    hits_before,
+                    cycle_hits,
+                };
+            }
+            seen[state] = step;
+            if chart.is_end[node as usize] {
+                hits.push(step);
+            }
+            node = chart.graph.neighbors(node)[turns[turn_idx]];
+            step += 1;
+        }
+    }
+
+    fn hits_at(&self, step: u64) -> bool {
+        if step < self.pre_period {
+            self.hits_before.contains(&step)
+        } else {
+            let offset = (step - self.pre_period) % self.cycle_len;
+            self.cycle_hits.contains(&(self.pre_period + offset))
         }
     }
-    steps as u64
 }
 
-/// Starting from all nodes that end in 'A', traverse the graph following the given
-/// turn directions until all of the nodes end in 'Z' and report the number of steps
-fn day08_p2(chart: &str) -> u64 {
-    // For this problem, we make very specific assumptions
-    // based off the structure of the *given* input (outside of the problem description).
-    // specifically each starting point --A puts you in a loop that contains a single --Z
-    // exit node. The cycle period happens to be the distance from --A to --Z. We find
-    // each cycle length and then compute their least common multiple (LCM).
-    let Chart { turns, connections } = Chart::from(chart);
-    let mut keys: Vec<String> = connections
-        .keys()
-        .filter(|key| key.as_bytes()[2] == b'A')
-        .map(|key| key.to_owned())
+/// Earliest step on which every ghost is on a --Z node at once,
+/// or None if that can never happen.
+pub fn earliest_arrival(chart: &str) -> Option<u64> {
+    let chart = Chart::from(chart);
+    let ghosts: Vec<GhostCycle> = chart
+        .graph
+        .ids()
+        .filter(|&id| chart.is_start[id as usize])
+        .map(|start| GhostCycle::trace(&chart, start))
         .collect();
-    let mut cycle_lens = vec![];
-    for (step, direction) in turns.chars().cycle().enumerate() {
-        keys = keys
+
+    // before every ghost is in its loop, just check each step directly
+    let settled = ghosts.iter().map(|ghost| ghost.pre_period).max()?;
+    if let Some(step) = (0..settled).find(|&step| ghosts.iter().all(|g| g.hits_at(step))) {
+        return Some(step);
+    }
+
+    // afterwards, every combination of one loop hit per ghost is a system of congruences
+    let mut solutions = vec![(0, 1)];
+    for ghost in ghosts.iter() {
+        let cycle_len = ghost.cycle_len as i128;
+        solutions = solutions
             .into_iter()
-            .map(|key| match direction {
-                'L' => connections.get(&key).unwrap().left.to_owned(),
-                'R' => connections.get(&key).unwrap().right.to_owned(),
-                _ => panic!("Unknown direction"),
-            })
-            .filter_map(|key| {
-                if key.as_bytes()[2] == b'Z' {
-                    cycle_lens.push(step as u64 + 1);
-                    None
-                } else {
-                    Some(key)
-                }
+            .flat_map(|solution| {
+                ghost.cycle_hits.iter().filter_map(move |&hit| {
+                    match crt(&[solution, (hit as i128, cycle_len)]) {
+                        Ok(solution) => Some(solution),
+                        Err(CrtError::Inconsistent) => None,
+                        Err(err) => panic!("Could not line up ghosts: {err:?}"),
+                    }
+                })
             })
             .collect();
-        if keys.is_empty() {
-            break;
-        }
     }
-    cycle_lens.into_iter().fold(1, lcm)
+    solutions
+        .into_iter()
+        .map(|(residue, modulus)| {
+            // smallest step >= settled with step = residue (mod modulus)
+            let settled = settled as i128;
+            let step = settled + (residue - settled).rem_euclid(modulus);
+            step as u64
+        })
+        .min()
+}
+
+/// Starting from all nodes that end in 'A', traverse the graph following the given
+/// turn directions until all of the nodes end in 'Z' and report the number of steps
+fn day08_p2(chart: &str) -> u64 {
+    // The given input happens to put each starting point --A in a loop that contains
+    // a single --Z exit node, with the loop period equal to the distance from --A
+    // to --Z, so the LCM of the distances is enough. That isn't true of every chart
+    // though, so we find the full loop structure of each ghost and line them up with
+    // the Chinese remainder theorem.
+    earliest_arrival(chart).expect("Ghosts never all reach --Z nodes at once")
 }
 
 pub fn run_day08_p1() -> u64 {
@@ -111,6 +247,11 @@ pub fn run_day08_p2() -> u64 {
 }
 
 fn main() {
+    if env::args().nth(1).as_deref() == Some("dot") {
+        let chart = read_to_string("data/day_08.txt").unwrap();
+        print!("{}", Chart::from(chart.as_str()).to_dot());
+        return;
+    }
     let part1 = if let Some(arg1) = env::args().nth(1) {
         arg1.parse().unwrap_or(1) == 1
     } else {
@@ -158,6 +299,101 @@ mod test {
         assert_eq!(day08_p2(EXAMPLE_2), 6)
     }
 
+    // ghost 1 only hits 11Z on steps 2, 5, 8, ... and ghost 2 only on odd steps,
+    // so the LCM of the first hits (2 and 1) would be wrong
+    const EXAMPLE_OFFSET: &str = "\
+        L\n\
+        \n\
+        11A = (11B, 11B)\n\
+        11B = (11Z, 11Z)\n\
+        11Z = (11C, 11C)\n\
+        11C = (11B, 11B)\n\
+        22A = (22Z, 22Z)\n\
+        22Z = (22B, 22B)\n\
+        22B = (22Z, 22Z)";
+
+    /// Steps every ghost together, for checking against
+    fn brute_force(chart: &str, limit: usize) -> Option<u64> {
+        let chart = Chart::from(chart);
+        let mut nodes: Vec<u32> = chart
+            .graph
+            .ids()
+            .filter(|&id| chart.is_start[id as usize])
+            .collect();
+        for (step, &turn) in chart.turns.iter().cycle().take(limit).enumerate() {
+            if nodes.iter().all(|&node| chart.is_end[node as usize]) {
+                return Some(step as u64);
+            }
+            for node in nodes.iter_mut() {
+                *node = chart.graph.neighbors(*node)[turn];
+            }
+        }
+        None
+    }
+
+    #[test]
+    fn test_day08_ghost_cycle() {
+        let chart = Chart::from(EXAMPLE_OFFSET);
+        assert_eq!(
+            GhostCycle::trace(&chart, chart.graph.id("11A").unwrap()),
+            GhostCycle {
+                pre_period: 1,
+                cycle_len: 3,
+                hits_before: vec![],
+                cycle_hits: vec![2],
+            }
+        );
+        let chart = Chart::from(EXAMPLE_2);
+        assert_eq!(
+            GhostCycle::trace(&chart, chart.graph.id("22A").unwrap()),
+            GhostCycle {
+                pre_period: 1,
+                cycle_len: 6,
+                hits_before: vec![],
+                cycle_hits: vec![3, 6],
+            }
+        );
+    }
+
+    #[test]
+    fn test_day08_earliest_arrival() {
+        assert_eq!(earliest_arrival(EXAMPLE_2), Some(6));
+        assert_eq!(earliest_arrival(EXAMPLE_OFFSET), Some(5));
+        assert_eq!(brute_force(EXAMPLE_OFFSET, 100), Some(5));
+        // a third ghost that's only on 33Z on even steps can never line up with 22A
+        let never =
+            format!("{EXAMPLE_OFFSET}\n33A = (33B, 33B)\n33B = (33Z, 33Z)\n33Z = (33B, 33B)");
+        assert_eq!(earliest_arrival(&never), None);
+        assert_eq!(brute_force(&never, 100), None);
+        // a chart where the answer comes before the ghosts settle into loops
+        let early = "L\n\n11A = (11Z, 11Z)\n11Z = (11B, 11B)\n11B = (11B, 11B)\n22A = (22Z, 22Z)\n22Z = (22Z, 22Z)";
+        assert_eq!(earliest_arrival(early), Some(1));
+    }
+
+    #[test]
+    fn test_day08_undefined_node() {
+        let err = Chart::new("LR\n\nAAA = (BBB, ZZZ)\nZZZ = (ZZZ, ZZZ)").err();
+        assert_eq!(err.as_deref(), Some("Node \"BBB\" is never defined"));
+        assert!(Chart::new("LX\n\nAAA = (AAA, AAA)").is_err());
+        assert!(Chart::new(EXAMPLE_2).is_ok());
+    }
+
+    #[test]
+    fn test_day08_dot() {
+        let dot = Chart::from(EXAMPLE).to_dot().to_string();
+        assert_eq!(
+            dot,
+            "digraph \"chart\" {\n    \
+                \"AAA\" [style=\"filled\", fillcolor=\"palegreen\"];\n    \
+                \"ZZZ\" [style=\"filled\", fillcolor=\"tomato\"];\n    \
+                \"AAA\" -> \"BBB\" [label=\"LR\"];\n    \
+                \"BBB\" -> \"AAA\" [label=\"L\"];\n    \
+                \"BBB\" -> \"ZZZ\" [label=\"R\"];\n    \
+                \"ZZZ\" -> \"ZZZ\" [label=\"LR\"];\n\
+            }\n"
+        );
+    }
+
     #[test]
     fn test_day08_p1() {
         assert_eq!(run_day08_p1(), 12737)
@@ -53,9 +53,9 @@ fn accumulate(mut x: Vec<i64>) -> Vec<i64> {
     x
 }
 
-fn day11_p2(data: &str, expand: i64) -> i64 {
-    // Idea: We could use the separability of the L1 metric to reduce this 2D problems
-    // into two 1D problems. I didn't do that here, but might be simpler, probably not faster.
+/// Galaxy (row, col) positions after each empty row and column has grown to
+/// `expand` copies of itself
+fn expanded_galaxies(data: &str, expand: i64) -> Vec<(i64, i64)> {
     let starmap: Vec<Vec<char>> = data.lines().map(|line| line.chars().collect()).collect();
     let width = starmap.first().unwrap().len();
     let mut xidx = vec![1; width];
@@ -81,6 +81,13 @@ fn day11_p2(data: &str, expand: i64) -> i64 {
             }
         }
     }
+    galaxies
+}
+
+fn day11_p2(data: &str, expand: i64) -> i64 {
+    // Idea: We could use the separability of the L1 metric to reduce this 2D problems
+    // into two 1D problems. I didn't do that here, but might be simpler, probably not faster.
+    let galaxies = expanded_galaxies(data, expand);
     let mut dist = 0;
     for g1 in galaxies.iter() {
         for g2 in galaxies.iter() {
@@ -91,6 +98,87 @@ fn day11_p2(data: &str, expand: i64) -> i64 {
     dist / 2
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Metric {
+    /// L1, the puzzle's own grid steps
+    Manhattan,
+    /// L-infinity, as if diagonal steps were allowed
+    Chebyshev,
+    /// L2, straight line
+    Euclidean,
+}
+
+impl Metric {
+    fn distance(self, a: (i64, i64), b: (i64, i64)) -> f64 {
+        let (dy, dx) = ((a.0 - b.0).abs(), (a.1 - b.1).abs());
+        match self {
+            Metric::Manhattan => (dy + dx) as f64,
+            Metric::Chebyshev => dy.max(dx) as f64,
+            Metric::Euclidean => (dy as f64).hypot(dx as f64),
+        }
+    }
+}
+
+impl std::str::FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "manhattan" => Ok(Metric::Manhattan),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            "euclidean" => Ok(Metric::Euclidean),
+            _ => Err(format!("Unknown metric {s:?}")),
+        }
+    }
+}
+
+/// The expanded galaxies and how far apart they all are
+struct GalaxyMap {
+    galaxies: Vec<(i64, i64)>,
+    metric: Metric,
+}
+
+impl GalaxyMap {
+    fn new(data: &str, expand: i64, metric: Metric) -> GalaxyMap {
+        GalaxyMap {
+            galaxies: expanded_galaxies(data, expand),
+            metric,
+        }
+    }
+
+    /// Distance between every pair, indexed like `galaxies`
+    fn distance_matrix(&self) -> Vec<Vec<f64>> {
+        self.galaxies
+            .iter()
+            .map(|&g1| {
+                self.galaxies
+                    .iter()
+                    .map(|&g2| self.metric.distance(g1, g2))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// For each galaxy, the `k` closest others as (index, distance), nearest
+    /// first with ties going to the lower index
+    fn nearest_neighbors(&self, k: usize) -> Vec<Vec<(usize, f64)>> {
+        self.distance_matrix()
+            .into_iter()
+            .enumerate()
+            .map(|(ii, row)| {
+                let mut others: Vec<(usize, f64)> = row
+                    .into_iter()
+                    .enumerate()
+                    .filter(|&(jj, _)| jj != ii)
+                    .collect();
+                others.sort_by(|a, b| a.1.total_cmp(&b.1).then(a.0.cmp(&b.0)));
+                others.truncate(k);
+                others
+            })
+            .collect()
+    }
+}
+
 pub fn run_day11_p1() -> i64 {
     let filename = "data/day_11.txt";
     let data = read_to_string(filename).unwrap();
@@ -103,7 +191,33 @@ pub fn run_day11_p2() -> i64 {
     day11_p2(&data, 1_000_000)
 }
 
+/// Print each galaxy's nearest neighbor for an expansion factor and metric
+pub fn run_day11_neighbors(expand: i64, metric: &str) -> Result<(), String> {
+    let metric = metric.parse()?;
+    let filename = "data/day_11.txt";
+    let data = read_to_string(filename).unwrap();
+    let map = GalaxyMap::new(&data, expand, metric);
+    for (ii, neighbors) in map.nearest_neighbors(1).iter().enumerate() {
+        let (row, col) = map.galaxies[ii];
+        if let Some(&(jj, dist)) = neighbors.first() {
+            println!("Galaxy {ii} at ({row}, {col}) is {dist} from galaxy {jj}");
+        }
+    }
+    Ok(())
+}
+
 fn main() {
+    if env::args().nth(1).as_deref() == Some("neighbors") {
+        let expand = env::args().nth(2).map_or(Ok(2), |x| x.parse());
+        let metric = env::args().nth(3).unwrap_or("manhattan".to_string());
+        let result = expand
+            .map_err(|e| format!("Bad expansion factor: {e}"))
+            .and_then(|expand| run_day11_neighbors(expand, &metric));
+        if let Err(e) = result {
+            eprintln!("{e}");
+        }
+        return;
+    }
     let part1 = if let Some(arg1) = env::args().nth(1) {
         arg1.parse().unwrap_or(1) == 1
     } else {
@@ -152,6 +266,49 @@ mod test {
         assert_eq!(day11_p2(EXAMPLE, 100), 8410)
     }
 
+    #[test]
+    fn test_day11_galaxy_map() {
+        let map = GalaxyMap::new(EXAMPLE, 2, Metric::Manhattan);
+        assert_eq!(map.galaxies.len(), 9);
+        assert_eq!(map.galaxies[0], (0, 4));
+        assert_eq!(map.galaxies[8], (11, 5));
+        let matrix = map.distance_matrix();
+        // the pairs called out in the puzzle, numbered from 1 there
+        assert_eq!(matrix[4][8], 9.0);
+        assert_eq!(matrix[0][6], 15.0);
+        assert_eq!(matrix[2][5], 17.0);
+        assert_eq!(matrix[7][8], 5.0);
+        assert_eq!(matrix[8][7], 5.0);
+        let total: f64 = matrix.iter().flatten().sum();
+        assert_eq!(total / 2.0, day11_p2(EXAMPLE, 2) as f64);
+
+        let nearest = map.nearest_neighbors(2);
+        assert_eq!(nearest[7], [(8, 5.0), (4, 6.0)]);
+        assert!(nearest.iter().all(|n| n.len() == 2));
+        assert_eq!(map.nearest_neighbors(100)[0].len(), 8);
+    }
+
+    #[test]
+    fn test_day11_metrics() {
+        let (a, b) = ((0, 4), (11, 5));
+        assert_eq!(Metric::Manhattan.distance(a, b), 12.0);
+        assert_eq!(Metric::Chebyshev.distance(a, b), 11.0);
+        assert_eq!(Metric::Euclidean.distance((0, 0), (3, -4)), 5.0);
+        assert_eq!("chebyshev".parse(), Ok(Metric::Chebyshev));
+        assert!("taxicab".parse::<Metric>().is_err());
+
+        let map = GalaxyMap::new(EXAMPLE, 10, Metric::Chebyshev);
+        let matrix = map.distance_matrix();
+        for (ii, row) in matrix.iter().enumerate() {
+            assert_eq!(row[ii], 0.0);
+            for (jj, &dist) in row.iter().enumerate() {
+                let manhattan = Metric::Manhattan.distance(map.galaxies[ii], map.galaxies[jj]);
+                let euclidean = Metric::Euclidean.distance(map.galaxies[ii], map.galaxies[jj]);
+                assert!(dist <= euclidean && euclidean <= manhattan);
+            }
+        }
+    }
+
     #[test]
     fn test_day11_p1() {
         assert_eq!(run_day11_p1(), 9233514)
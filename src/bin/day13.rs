@@ -1,79 +1,98 @@
 //! Day 13: Point of Incidence
-#![allow(clippy::needless_range_loop)]
 use std::env;
 use std::fs::read_to_string;
 use std::time::Instant;
 
-fn day13_p1_single(chart: &[&[u8]]) -> u64 {
-    let height = chart.len();
-    let width = chart[0].len();
-
-    // find horizontal lines of symmetry (matching rows)
-    'outer: for jj in 0..(height - 1) {
-        for offset in 0..usize::min(jj + 1, height - 1 - jj) {
-            for ii in 0..width {
-                if chart[jj - offset][ii] != chart[jj + 1 + offset][ii] {
-                    continue 'outer;
-                }
-            }
-        }
-        return ((jj + 1) * 100) as u64;
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Axis {
+    /// a line between two rows
+    Horizontal,
+    /// a line between two columns
+    Vertical,
+}
 
-    // find vertical lines of symmetry (matching columns)
-    'outer2: for jj in 0..(width - 1) {
-        for offset in 0..usize::min(jj + 1, width - 1 - jj) {
-            for ii in 0..height {
-                if chart[ii][jj - offset] != chart[ii][jj + 1 + offset] {
-                    continue 'outer2;
-                }
-            }
+/// A line the chart (nearly) reflects across, with `after` rows or columns
+/// above or left of it, and how many tiles don't match their reflection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Reflection {
+    axis: Axis,
+    after: usize,
+    mismatches: u32,
+}
+
+impl Reflection {
+    /// The number the puzzle wants for this line
+    fn summary(&self) -> u64 {
+        match self.axis {
+            Axis::Horizontal => 100 * self.after as u64,
+            Axis::Vertical => self.after as u64,
         }
-        return (jj + 1) as u64;
     }
-    0
 }
 
-fn day13_p2_single(chart: &[&[u8]]) -> u64 {
-    let height = chart.len();
-    let width = chart[0].len();
-
-    // find horizontal lines of symmetry (matching rows)
-    'outer: for jj in 0..(height - 1) {
-        let mut smudge_count = 0;
-        for offset in 0..usize::min(jj + 1, height - 1 - jj) {
-            for ii in 0..width {
-                if chart[jj - offset][ii] != chart[jj + 1 + offset][ii] {
-                    smudge_count += 1;
-                    if smudge_count > 1 {
-                        continue 'outer;
-                    }
-                }
+/// Each row, and each column, as a bitmask of where the rocks are
+fn encode(chart: &[&[u8]]) -> (Vec<u64>, Vec<u64>) {
+    let (height, width) = (chart.len(), chart[0].len());
+    assert!(height <= 64 && width <= 64, "Chart too big for u64 masks");
+    let mut rows = vec![0u64; height];
+    let mut cols = vec![0u64; width];
+    for (row, line) in chart.iter().enumerate() {
+        for (col, &tile) in line.iter().enumerate() {
+            if tile == b'#' {
+                rows[row] |= 1 << col;
+                cols[col] |= 1 << row;
             }
         }
-        if smudge_count == 1 {
-            return ((jj + 1) * 100) as u64;
-        }
     }
+    (rows, cols)
+}
 
-    // find vertical lines of symmetry (matching columns)
-    'outer2: for jj in 0..(width - 1) {
-        let mut smudge_count = 0;
-        for offset in 0..usize::min(jj + 1, width - 1 - jj) {
-            for ii in 0..height {
-                if chart[ii][jj - offset] != chart[ii][jj + 1 + offset] {
-                    smudge_count += 1;
-                    if smudge_count > 1 {
-                        continue 'outer2;
-                    }
+/// Splits of `lines` that reflect with at most `tolerance` differing tiles,
+/// along with how many there are
+fn splits_within(lines: &[u64], tolerance: u32) -> Vec<(usize, u32)> {
+    (1..lines.len())
+        .filter_map(|after| {
+            let mut mismatches = 0;
+            for (a, b) in lines[..after].iter().rev().zip(&lines[after..]) {
+                mismatches += (a ^ b).count_ones();
+                if mismatches > tolerance {
+                    return None;
                 }
             }
-        }
-        if smudge_count == 1 {
-            return (jj + 1) as u64;
-        }
-    }
-    0
+            Some((after, mismatches))
+        })
+        .collect()
+}
+
+/// Every horizontal and then vertical line the chart reflects across with
+/// at most `tolerance` tiles out of place
+fn reflections(chart: &[&[u8]], tolerance: u32) -> Vec<Reflection> {
+    let (rows, cols) = encode(chart);
+    let lines = |lines: &[u64], axis| {
+        splits_within(lines, tolerance)
+            .into_iter()
+            .map(move |(after, mismatches)| Reflection {
+                axis,
+                after,
+                mismatches,
+            })
+    };
+    lines(&rows, Axis::Horizontal)
+        .chain(lines(&cols, Axis::Vertical))
+        .collect()
+}
+
+fn day13_p1_single(chart: &[&[u8]]) -> u64 {
+    reflections(chart, 0).first().map_or(0, Reflection::summary)
+}
+
+/// The one smudge means the new line is off by exactly one tile, which
+/// also rules out the old, perfect, line
+fn day13_p2_single(chart: &[&[u8]]) -> u64 {
+    reflections(chart, 1)
+        .into_iter()
+        .find(|reflection| reflection.mismatches == 1)
+        .map_or(0, |reflection| reflection.summary())
 }
 
 fn day13_p1(data: &str) -> u64 {
@@ -156,6 +175,32 @@ mod test {
         assert_eq!(day13_p2(EXAMPLE), 400)
     }
 
+    #[test]
+    fn test_day13_reflections() {
+        let charts: Vec<Vec<&[u8]>> = EXAMPLE
+            .split("\n\n")
+            .map(|chart| chart.lines().map(|line| line.as_bytes()).collect())
+            .collect();
+        let line = |axis, after, mismatches| Reflection {
+            axis,
+            after,
+            mismatches,
+        };
+        assert_eq!(reflections(&charts[0], 0), [line(Axis::Vertical, 5, 0)]);
+        assert_eq!(
+            reflections(&charts[0], 1),
+            [line(Axis::Horizontal, 3, 1), line(Axis::Vertical, 5, 0)]
+        );
+        assert_eq!(
+            reflections(&charts[1], 1),
+            [line(Axis::Horizontal, 1, 1), line(Axis::Horizontal, 4, 0)]
+        );
+        // with enough tolerance every split counts
+        let all = reflections(&charts[1], 64);
+        assert_eq!(all.len(), 6 + 8);
+        assert_eq!(all[0], line(Axis::Horizontal, 1, 1));
+    }
+
     #[test]
     fn test_day13_p1() {
         assert_eq!(run_day13_p1(), 31739);
@@ -1,12 +1,12 @@
 //! Day 16: The Floor Will Be Lava
-use rayon::prelude::*;
-use std::collections::HashSet;
+use aoc_2023::graph::strongly_connected_components;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fs::read_to_string;
 use std::time::Instant;
 
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
-enum Direction {
+pub enum Direction {
     Up,
     Down,
     Left,
@@ -15,7 +15,7 @@ enum Direction {
 use Direction::*;
 
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
-struct Coordinate {
+pub struct Coordinate {
     row: usize,
     col: usize,
 }
@@ -75,9 +75,181 @@ impl From<(usize, usize)> for Coordinate {
     }
 }
 
-fn run_beam_sim(chart: &[&[u8]], initial: (Coordinate, Direction)) -> usize {
-    let height = chart.len();
-    let width = chart[0].len();
+impl Coordinate {
+    fn step(&self, dir: Direction, height: usize, width: usize) -> Option<Self> {
+        match dir {
+            Up => self.up(),
+            Down => self.down(height),
+            Left => self.left(),
+            Right => self.right(width),
+        }
+    }
+}
+
+impl Direction {
+    fn index(self) -> usize {
+        match self {
+            Up => 0,
+            Down => 1,
+            Left => 2,
+            Right => 3,
+        }
+    }
+}
+
+impl TryFrom<char> for Direction {
+    type Error = String;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            'U' => Ok(Up),
+            'D' => Ok(Down),
+            'L' => Ok(Left),
+            'R' => Ok(Right),
+            c => Err(format!("Unknown direction {c:?}")),
+        }
+    }
+}
+
+/// The puzzle's own components. Each line of a component spec is a tile
+/// followed by how it turns a beam arriving going each way, e.g. `L>UD` for
+/// a beam going left coming out going both up and down. Beams arriving any
+/// way that isn't listed are absorbed. A tile with `portal` instead is one
+/// of a pair, with beams going in one carrying on out of the other.
+pub const STANDARD_COMPONENTS: &str = r"
+. U>U D>D L>L R>R
+| U>U D>D L>UD R>UD
+- U>LR D>LR L>L R>R
+/ U>R D>L L>D R>U
+\ U>L D>R L>U R>D
+";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Behavior {
+    /// directions out for a beam arriving going each direction
+    Deflect([Vec<Direction>; 4]),
+    Portal,
+}
+
+fn parse_components(spec: &str) -> Result<HashMap<u8, Behavior>, String> {
+    let mut components = HashMap::new();
+    for line in spec.lines().filter(|line| !line.trim().is_empty()) {
+        let mut tokens = line.split_whitespace();
+        let tile = match tokens.next().unwrap().as_bytes() {
+            &[tile] => tile,
+            _ => return Err(format!("Components must be a single character: {line:?}")),
+        };
+        let rules: Vec<&str> = tokens.collect();
+        let behavior = if rules == ["portal"] {
+            Behavior::Portal
+        } else {
+            let mut outputs: [Vec<Direction>; 4] = Default::default();
+            for rule in rules {
+                let Some((from, to)) = rule.split_once('>') else {
+                    return Err(format!("Expected a rule like L>UD, got {rule:?}"));
+                };
+                let mut from = from.chars();
+                let (Some(from), None) = (from.next(), from.next()) else {
+                    return Err(format!("Expected a single direction in {rule:?}"));
+                };
+                outputs[Direction::try_from(from)?.index()] = to
+                    .chars()
+                    .map(Direction::try_from)
+                    .collect::<Result<_, _>>()?;
+            }
+            Behavior::Deflect(outputs)
+        };
+        // later lines win so a spec can be added on to the standard one
+        components.insert(tile, behavior);
+    }
+    Ok(components)
+}
+
+/// A layout of components along with what each one does
+pub struct Contraption {
+    chart: Vec<Vec<u8>>,
+    components: HashMap<u8, Behavior>,
+    /// the other end of each portal
+    portals: HashMap<Coordinate, Coordinate>,
+}
+
+impl Contraption {
+    pub fn new(data: &str, spec: &str) -> Result<Self, String> {
+        let chart: Vec<Vec<u8>> = data.lines().map(|line| line.as_bytes().to_vec()).collect();
+        let components = parse_components(spec)?;
+        let mut ends: HashMap<u8, Vec<Coordinate>> = HashMap::new();
+        for (row, line) in chart.iter().enumerate() {
+            for (col, tile) in line.iter().enumerate() {
+                match components.get(tile) {
+                    None => return Err(format!("No component {:?}", *tile as char)),
+                    Some(Behavior::Portal) => {
+                        ends.entry(*tile).or_default().push((row, col).into())
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+        let mut portals = HashMap::new();
+        for (tile, ends) in ends {
+            let &[a, b] = ends.as_slice() else {
+                return Err(format!("Portal {:?} needs exactly two ends", tile as char));
+            };
+            portals.insert(a, b);
+            portals.insert(b, a);
+        }
+        Ok(Self {
+            chart,
+            components,
+            portals,
+        })
+    }
+
+    fn height(&self) -> usize {
+        self.chart.len()
+    }
+
+    fn width(&self) -> usize {
+        self.chart[0].len()
+    }
+
+    fn behavior(&self, tile: Coordinate) -> &Behavior {
+        &self.components[&self.chart[tile.row][tile.col]]
+    }
+
+    /// Whether a beam arriving at `tile` going `dir` just carries on
+    fn passes_straight(&self, tile: Coordinate, dir: Direction) -> bool {
+        match self.behavior(tile) {
+            Behavior::Deflect(outputs) => outputs[dir.index()] == [dir],
+            Behavior::Portal => false,
+        }
+    }
+
+    /// Where a beam arriving at `tile` going `dir` goes next, along with the
+    /// far end of the portal if that's what it went through
+    fn exits(
+        &self,
+        tile: Coordinate,
+        dir: Direction,
+    ) -> (Vec<(Coordinate, Direction)>, Option<Coordinate>) {
+        let (height, width) = (self.height(), self.width());
+        match self.behavior(tile) {
+            Behavior::Deflect(outputs) => {
+                let exits = outputs[dir.index()]
+                    .iter()
+                    .filter_map(|&dir| Some((tile.step(dir, height, width)?, dir)))
+                    .collect();
+                (exits, None)
+            }
+            Behavior::Portal => {
+                let other = self.portals[&tile];
+                let exits = other.step(dir, height, width).map(|next| (next, dir));
+                (exits.into_iter().collect(), Some(other))
+            }
+        }
+    }
+}
+
+fn run_beam_sim(contraption: &Contraption, initial: (Coordinate, Direction)) -> usize {
     // these are the tiles that we need to process (potential wave fronts)
     let mut visit_stack = vec![initial];
     // these are all wave fronts we have already seen (to avoid loops)
@@ -92,93 +264,187 @@ fn run_beam_sim(chart: &[&[u8]], initial: (Coordinate, Direction)) -> usize {
             continue;
         }
         visited.insert((tile, dir));
-        let component = chart[tile.row][tile.col];
-        match (component, dir) {
-            (b'.' | b'|', Up) | (b'/', Right) | (b'\\', Left) => {
-                if let Some(tile) = tile.up() {
-                    visit_stack.push((tile, Up));
+        let (exits, portal) = contraption.exits(tile, dir);
+        energized.extend(portal);
+        visit_stack.extend(exits);
+    }
+    energized.len()
+}
+
+/// One bit per tile of the chart
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct TileSet {
+    width: usize,
+    bits: Vec<u64>,
+}
+
+impl TileSet {
+    fn new(height: usize, width: usize) -> Self {
+        Self {
+            width,
+            bits: vec![0; (height * width).div_ceil(64)],
+        }
+    }
+
+    fn insert(&mut self, tile: Coordinate) {
+        let idx = tile.row * self.width + tile.col;
+        self.bits[idx / 64] |= 1 << (idx % 64);
+    }
+
+    fn union_with(&mut self, other: &Self) {
+        for (bits, other) in self.bits.iter_mut().zip(&other.bits) {
+            *bits |= other;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.bits
+            .iter()
+            .map(|bits| bits.count_ones() as usize)
+            .sum()
+    }
+}
+
+/// Energizes the tiles from `tile` onwards going `dir`, up to and including
+/// the first one that does anything but let the beam through, which is
+/// returned along with the direction the beam was going when it got there
+fn straight_run(
+    contraption: &Contraption,
+    mut tile: Coordinate,
+    dir: Direction,
+    energized: &mut TileSet,
+) -> Option<(Coordinate, Direction)> {
+    loop {
+        energized.insert(tile);
+        if !contraption.passes_straight(tile, dir) {
+            return Some((tile, dir));
+        }
+        tile = tile.step(dir, contraption.height(), contraption.width())?;
+    }
+}
+
+/// Where to shine the beam in from, and how many tiles that energizes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub tile: Coordinate,
+    pub dir: Direction,
+    pub energized: usize,
+}
+
+/// Beam propagation worked out once for the whole contraption. A beam
+/// arriving at a tile going a way that it doesn't just let through is a
+/// node, with edges along the straight runs to the next such tiles it hits.
+/// Runs carry on through empty tiles, splitters hit edge-on, diodes going
+/// their way, and any other tile that passes the beam straight on. Every
+/// node in a strongly connected component energizes the same tiles, so each
+/// of those gets a single set, built up from the sets of the components
+/// downstream of it.
+struct BeamGraph {
+    /// node for a beam arriving at a component going a direction
+    nodes: HashMap<(Coordinate, Direction), usize>,
+    /// strongly connected component of each node
+    scc: Vec<usize>,
+    /// every tile energized by a beam arriving in each component
+    energized: Vec<TileSet>,
+}
+
+impl BeamGraph {
+    fn new(contraption: &Contraption) -> Self {
+        let (height, width) = (contraption.height(), contraption.width());
+        let mut nodes = HashMap::new();
+        for row in 0..height {
+            for col in 0..width {
+                for dir in [Up, Down, Left, Right] {
+                    let tile = (row, col).into();
+                    if !contraption.passes_straight(tile, dir) {
+                        nodes.insert((tile, dir), nodes.len());
+                    }
                 }
             }
-            (b'.' | b'|', Down) | (b'/', Left) | (b'\\', Right) => {
-                if let Some(tile) = tile.down(height) {
-                    visit_stack.push((tile, Down));
-                }
+        }
+        // the tiles each node energizes on its own, and the nodes it leads to
+        let mut own = vec![TileSet::new(height, width); nodes.len()];
+        let mut edges = vec![vec![]; nodes.len()];
+        for (&(tile, dir), &node) in &nodes {
+            own[node].insert(tile);
+            let (exits, portal) = contraption.exits(tile, dir);
+            if let Some(other) = portal {
+                own[node].insert(other);
             }
-            (b'.' | b'-', Left) | (b'/', Down) | (b'\\', Up) => {
-                if let Some(tile) = tile.left() {
-                    visit_stack.push((tile, Left));
+            for (next, dir) in exits {
+                if let Some(hit) = straight_run(contraption, next, dir, &mut own[node]) {
+                    edges[node].push(nodes[&hit]);
                 }
             }
-            (b'.' | b'-', Right) | (b'/', Up) | (b'\\', Down) => {
-                if let Some(tile) = tile.right(width) {
-                    visit_stack.push((tile, Right));
-                }
+        }
+
+        let components = strongly_connected_components(&edges);
+        let mut scc = vec![0; nodes.len()];
+        for (id, members) in components.iter().enumerate() {
+            for &member in members {
+                scc[member] = id;
             }
-            (b'|', Left | Right) => {
-                if let Some(tile) = tile.up() {
-                    visit_stack.push((tile, Up));
-                }
-                if let Some(tile) = tile.down(height) {
-                    visit_stack.push((tile, Down));
+        }
+        // components come after everything downstream of them, so those
+        // sets are all ready by the time they're needed
+        let mut energized: Vec<TileSet> = vec![];
+        for (id, members) in components.iter().enumerate() {
+            let mut tiles = TileSet::new(height, width);
+            for &member in members {
+                tiles.union_with(&own[member]);
+                for &next in &edges[member] {
+                    if scc[next] != id {
+                        tiles.union_with(&energized[scc[next]]);
+                    }
                 }
             }
-            (b'-', Up | Down) => {
-                if let Some(tile) = tile.left() {
-                    visit_stack.push((tile, Left));
-                }
-                if let Some(tile) = tile.right(width) {
-                    visit_stack.push((tile, Right));
+            energized.push(tiles);
+        }
+        Self {
+            nodes,
+            scc,
+            energized,
+        }
+    }
+
+    /// Number of tiles energized by a beam coming in at `tile` going `dir`
+    fn energized_from(&self, contraption: &Contraption, tile: Coordinate, dir: Direction) -> usize {
+        let mut energized = TileSet::new(contraption.height(), contraption.width());
+        if let Some(hit) = straight_run(contraption, tile, dir, &mut energized) {
+            energized.union_with(&self.energized[self.scc[self.nodes[&hit]]]);
+        }
+        energized.len()
+    }
+
+    /// The best of the tiles around the edge to shine the beam in from
+    fn best_entry(&self, contraption: &Contraption) -> Entry {
+        let (height, width) = (contraption.height(), contraption.width());
+        (0..width)
+            .map(|col| ((0, col), Down))
+            .chain((0..width).map(|col| ((height - 1, col), Up)))
+            .chain((0..height).map(|row| ((row, 0), Right)))
+            .chain((0..height).map(|row| ((row, width - 1), Left)))
+            .map(|(tile, dir)| {
+                let tile = tile.into();
+                Entry {
+                    tile,
+                    dir,
+                    energized: self.energized_from(contraption, tile, dir),
                 }
-            }
-            _ => unreachable!(),
-        }
-    }
-    // for row in 0..height {
-    //     for col in 0..width {
-    //         let c = if energized.contains(&(row, col).into()) {
-    //             '#'
-    //         } else {
-    //             '.'
-    //         };
-    //         print!("{c}");
-    //     }
-    //     println!();
-    // }
-    energized.len()
+            })
+            .max_by_key(|entry| entry.energized)
+            .unwrap()
+    }
 }
 
 fn day16_p1(data: &str) -> usize {
-    let chart = data.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
-    run_beam_sim(&chart, ((0, 0).into(), Right))
-}
-
-fn day16_p2(data: &str) -> usize {
-    // this runs in about 4 seconds without parallelization, but that's
-    // a tad annoying for unit testing so I threw in rayon to speed it up 8x
-    let chart = data.lines().map(|line| line.as_bytes()).collect::<Vec<_>>();
-    let height = chart.len();
-    let width = chart[0].len();
-    (0..width)
-        .into_par_iter()
-        .map(|col| ((0, col), Down))
-        .chain(
-            (0..width)
-                .into_par_iter()
-                .map(|col| ((height - 1, col), Up))
-                .chain(
-                    (0..height)
-                        .into_par_iter()
-                        .map(|row| ((row, 0), Right))
-                        .chain(
-                            (0..height)
-                                .into_par_iter()
-                                .map(|row| ((row, width - 1), Left)),
-                        ),
-                ),
-        )
-        .map(|(pos, dir)| run_beam_sim(&chart, (pos.into(), dir)))
-        .max()
-        .unwrap()
+    let contraption = Contraption::new(data, STANDARD_COMPONENTS).unwrap();
+    run_beam_sim(&contraption, ((0, 0).into(), Right))
+}
+
+fn day16_p2(data: &str) -> Entry {
+    let contraption = Contraption::new(data, STANDARD_COMPONENTS).unwrap();
+    BeamGraph::new(&contraption).best_entry(&contraption)
 }
 
 pub fn run_day16_p1() -> usize {
@@ -190,7 +456,7 @@ pub fn run_day16_p1() -> usize {
 pub fn run_day16_p2() -> usize {
     let filename = "data/day_16.txt";
     let data = read_to_string(filename).unwrap();
-    day16_p2(&data)
+    day16_p2(&data).energized
 }
 
 fn main() {
@@ -234,7 +500,76 @@ mod test {
 
     #[test]
     fn test_day16_p2_example() {
-        assert_eq!(day16_p2(EXAMPLE), 51)
+        let entry = day16_p2(EXAMPLE);
+        assert_eq!(entry.energized, 51);
+        assert_eq!((entry.tile, entry.dir), ((0, 3).into(), Down));
+    }
+
+    /// Every way into the contraption energizes the same tiles whether
+    /// simulated directly or looked up in the beam graph
+    fn check_beam_graph(contraption: &Contraption) {
+        let graph = BeamGraph::new(contraption);
+        for row in 0..contraption.height() {
+            for col in 0..contraption.width() {
+                for dir in [Up, Down, Left, Right] {
+                    let tile = (row, col).into();
+                    assert_eq!(
+                        graph.energized_from(contraption, tile, dir),
+                        run_beam_sim(contraption, (tile, dir)),
+                        "{tile:?} {dir:?}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_day16_beam_graph() {
+        let contraption = Contraption::new(EXAMPLE, STANDARD_COMPONENTS).unwrap();
+        let graph = BeamGraph::new(&contraption);
+        // the loops through the splitters collapse into far fewer sets
+        assert!(graph.energized.len() < graph.nodes.len());
+        check_beam_graph(&contraption);
+    }
+
+    /// Absorbers, a diode that only lets beams going right through, a half
+    /// silvered mirror and a pair of portals
+    const EXTENDED: &str = "
+#
+> R>R
+% U>UL D>DR L>LU R>RD
+@ portal";
+
+    #[test]
+    fn test_day16_extended_components() {
+        let spec = format!("{STANDARD_COMPONENTS}{EXTENDED}");
+        let run = |data: &str, dir| {
+            let contraption = Contraption::new(data, &spec).unwrap();
+            run_beam_sim(&contraption, ((0, 0).into(), dir))
+        };
+        assert_eq!(run("..#..", Right), 3);
+        assert_eq!(run(".>..", Right), 4);
+        assert_eq!(run("..>.", Left), 1);
+        // reflected down and passed straight on
+        assert_eq!(run("%..\n...\n...", Right), 5);
+        // through the portal and out the other side
+        assert_eq!(run(".@.\n#..\n.@.", Right), 4);
+        // and round through the portals again the other way
+        assert_eq!(run(".@\\\n...\n/@/", Right), 7);
+
+        let contraption = Contraption::new(
+            r".|.@.\
+.%.>..
+#-./.@
+.\>%..",
+            &spec,
+        )
+        .unwrap();
+        check_beam_graph(&contraption);
+
+        assert!(Contraption::new("..x..", &spec).is_err());
+        assert!(Contraption::new(".@.", &spec).is_err());
+        assert!(Contraption::new("..", "? Q>U").is_err());
     }
 
     #[test]
@@ -1,22 +1,92 @@
 //! Day 19: Aplenty
+use aoc_2023::graph::strongly_connected_components;
 use regex::Regex;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::env;
 use std::fs::read_to_string;
 use std::time::Instant;
 
-#[derive(Debug)]
+/// Half-open `start..end` run of attribute values, wide enough that the end
+/// can sit one past `u32::MAX`
+type Interval = (u64, u64);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
 enum Operator {
-    LessThan(u32),
-    GreaterThan(u32),
+    LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+}
+
+impl From<&str> for Operator {
+    fn from(data: &str) -> Self {
+        match data {
+            "<" => Operator::LessThan,
+            "<=" => Operator::LessEqual,
+            ">" => Operator::GreaterThan,
+            ">=" => Operator::GreaterEqual,
+            "==" => Operator::Equal,
+            "!=" => Operator::NotEqual,
+            _ => panic!("Unrecognized operator {data:?}"),
+        }
+    }
+}
+
+impl Operator {
+    fn eval(self, lhs: u32, rhs: u32) -> bool {
+        match self {
+            Operator::LessThan => lhs < rhs,
+            Operator::LessEqual => lhs <= rhs,
+            Operator::GreaterThan => lhs > rhs,
+            Operator::GreaterEqual => lhs >= rhs,
+            Operator::Equal => lhs == rhs,
+            Operator::NotEqual => lhs != rhs,
+        }
+    }
+
+    /// Splits the half-open interval `lo..hi` into the parts where `x op val`
+    /// holds and the parts where it doesn't. Empty pieces are dropped and
+    /// touching pieces are merged.
+    fn split(self, (lo, hi): Interval, val: u32) -> (Vec<Interval>, Vec<Interval>) {
+        let val = u64::from(val);
+        let clamp = |x: u64| x.clamp(lo, hi);
+        // every operator cuts the line into at most these three pieces
+        let below = (lo, clamp(val));
+        let at = (clamp(val), clamp(val + 1));
+        let above = (clamp(val + 1), hi);
+        let (pass, fail) = match self {
+            Operator::LessThan => (vec![below], vec![at, above]),
+            Operator::LessEqual => (vec![below, at], vec![above]),
+            Operator::GreaterThan => (vec![above], vec![below, at]),
+            Operator::GreaterEqual => (vec![at, above], vec![below]),
+            Operator::Equal => (vec![at], vec![below, above]),
+            Operator::NotEqual => (vec![below, above], vec![at]),
+        };
+        let tidy = |pieces: Vec<Interval>| {
+            pieces.into_iter().filter(|(start, end)| start < end).fold(
+                vec![],
+                |mut acc: Vec<Interval>, piece| {
+                    match acc.last_mut() {
+                        Some(last) if last.1 == piece.0 => last.1 = piece.1,
+                        _ => acc.push(piece),
+                    }
+                    acc
+                },
+            )
+        };
+        (tidy(pass), tidy(fail))
+    }
 }
 
 #[derive(Debug)]
 enum Condition {
-    X(Operator),
-    M(Operator),
-    A(Operator),
-    S(Operator),
+    Compare {
+        attribute: String,
+        operator: Operator,
+        value: u32,
+    },
     True,
 }
 
@@ -35,23 +105,14 @@ impl From<&str> for Conditional {
                 result: data.to_string(),
             };
         }
-        let re = Regex::new(r"([x,m,a,s])([>,<])([0-9]+):([a-zA-Z]+)$").unwrap();
+        let re = Regex::new(r"^([a-zA-Z]+)(<=|>=|==|!=|<|>)([0-9]+):([a-zA-Z]+)$").unwrap();
         let captures = re.captures(data.trim()).unwrap();
-        let operand = captures[3].parse().unwrap();
-        let operator = match &captures[2] {
-            ">" => Operator::GreaterThan(operand),
-            "<" => Operator::LessThan(operand),
-            _ => unreachable!(),
-        };
-        let condition = match &captures[1] {
-            "x" => Condition::X(operator),
-            "m" => Condition::M(operator),
-            "a" => Condition::A(operator),
-            "s" => Condition::S(operator),
-            _ => unreachable!(),
-        };
         Self {
-            condition,
+            condition: Condition::Compare {
+                attribute: captures[1].to_string(),
+                operator: Operator::from(&captures[2]),
+                value: captures[3].parse().unwrap(),
+            },
             result: captures[4].to_string(),
         }
     }
@@ -59,15 +120,12 @@ impl From<&str> for Conditional {
 
 impl Conditional {
     fn eval(&self, gizmo: &Gizmo) -> Option<String> {
-        let cond = match self.condition {
-            Condition::X(Operator::LessThan(val)) => gizmo.x < val,
-            Condition::X(Operator::GreaterThan(val)) => gizmo.x > val,
-            Condition::M(Operator::LessThan(val)) => gizmo.m < val,
-            Condition::M(Operator::GreaterThan(val)) => gizmo.m > val,
-            Condition::A(Operator::LessThan(val)) => gizmo.a < val,
-            Condition::A(Operator::GreaterThan(val)) => gizmo.a > val,
-            Condition::S(Operator::LessThan(val)) => gizmo.s < val,
-            Condition::S(Operator::GreaterThan(val)) => gizmo.s > val,
+        let cond = match &self.condition {
+            Condition::Compare {
+                attribute,
+                operator,
+                value,
+            } => operator.eval(gizmo.get(attribute), *value),
             Condition::True => true,
         };
         if cond {
@@ -76,40 +134,52 @@ impl Conditional {
             None
         }
     }
+
+    fn attribute(&self) -> Option<&str> {
+        match &self.condition {
+            Condition::Compare { attribute, .. } => Some(attribute),
+            Condition::True => None,
+        }
+    }
 }
 
 #[derive(Debug)]
 struct Gizmo {
-    x: u32,
-    m: u32,
-    a: u32,
-    s: u32,
+    attributes: HashMap<String, u32>,
 }
 
 impl From<&str> for Gizmo {
     fn from(data: &str) -> Self {
         // "{x=787,m=2655,a=1222,s=2876}"
-        let re = Regex::new(r"\{x=([0-9]+),m=([0-9]+),a=([0-9]+),s=([0-9]+)\}$").unwrap();
+        let re = Regex::new(r"^\{(.*)\}$").unwrap();
         let captures = re.captures(data.trim()).unwrap();
-        Self {
-            x: captures[1].parse().unwrap(),
-            m: captures[2].parse().unwrap(),
-            a: captures[3].parse().unwrap(),
-            s: captures[4].parse().unwrap(),
-        }
+        let attributes = captures[1]
+            .split(',')
+            .map(|pair| {
+                let (name, val) = pair.split_once('=').unwrap();
+                (name.trim().to_string(), val.trim().parse().unwrap())
+            })
+            .collect();
+        Self { attributes }
     }
 }
 
 impl Gizmo {
+    fn get(&self, attribute: &str) -> u32 {
+        match self.attributes.get(attribute) {
+            Some(&val) => val,
+            None => panic!("Part has no attribute {attribute:?}"),
+        }
+    }
+
     fn sum(&self) -> u32 {
-        self.x + self.m + self.a + self.s
+        self.attributes.values().sum()
     }
 }
 
-fn day19_p1(data: &str) -> u32 {
-    let (ruleset, gizmos) = data.split_once("\n\n").unwrap();
+fn parse_ruleset(ruleset: &str) -> HashMap<String, Vec<Conditional>> {
     let re = Regex::new(r"([a-zA-Z]+)\{(.+)\}$").unwrap();
-    let ruleset: HashMap<String, Vec<Conditional>> = ruleset
+    ruleset
         .lines()
         .map(|line| {
             let captures = re.captures(line.trim()).unwrap();
@@ -117,7 +187,27 @@ fn day19_p1(data: &str) -> u32 {
             let val = captures[2].split(',').map(Conditional::from).collect();
             (key, val)
         })
-        .collect();
+        .collect()
+}
+
+/// Every attribute name mentioned either by a part or by a rule
+fn attribute_names(ruleset: &HashMap<String, Vec<Conditional>>, gizmos: &str) -> BTreeSet<String> {
+    gizmos
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .flat_map(|line| Gizmo::from(line).attributes.into_keys())
+        .chain(
+            ruleset
+                .values()
+                .flatten()
+                .filter_map(|rule| rule.attribute().map(str::to_string)),
+        )
+        .collect()
+}
+
+fn day19_p1(data: &str) -> u32 {
+    let (ruleset, gizmos) = data.split_once("\n\n").unwrap();
+    let ruleset = parse_ruleset(ruleset);
     gizmos
         .lines()
         .map(Gizmo::from)
@@ -138,180 +228,89 @@ fn day19_p1(data: &str) -> u32 {
         .sum()
 }
 
-#[derive(Debug)]
-struct GizmoRange {
-    x: (u32, u32),
-    m: (u32, u32),
-    a: (u32, u32),
-    s: (u32, u32),
+/// A box of parts, with each attribute limited to a half-open range
+#[derive(Debug, Clone, PartialEq)]
+pub struct GizmoRange {
+    bounds: BTreeMap<String, Interval>,
 }
 
 impl GizmoRange {
-    fn new() -> Self {
+    /// Every attribute allowed to take any value in `min..=max`
+    pub fn new<S: AsRef<str>>(attributes: impl IntoIterator<Item = S>, min: u32, max: u32) -> Self {
         Self {
-            x: (1, 4001),
-            m: (1, 4001),
-            a: (1, 4001),
-            s: (1, 4001),
+            bounds: attributes
+                .into_iter()
+                .map(|name| (name.as_ref().to_string(), (min.into(), u64::from(max) + 1)))
+                .collect(),
         }
     }
 
+    /// Limits (or adds) a single attribute to `min..=max`
+    pub fn with_bounds(mut self, attribute: &str, min: u32, max: u32) -> Self {
+        self.bounds
+            .insert(attribute.to_string(), (min.into(), u64::from(max) + 1));
+        self
+    }
+
     fn is_empty(&self) -> bool {
-        (self.x.1 <= self.x.0)
-            || (self.m.1 <= self.m.0)
-            || (self.a.1 <= self.a.0)
-            || (self.s.1 <= self.s.0)
+        self.bounds.values().any(|(lo, hi)| hi <= lo)
     }
 
     fn distinct(&self) -> u64 {
         if self.is_empty() {
             return 0;
         }
-        (self.x.1 - self.x.0) as u64
-            * (self.m.1 - self.m.0) as u64
-            * (self.a.1 - self.a.0) as u64
-            * (self.s.1 - self.s.0) as u64
-    }
-
-    fn apply_conditional(self, cond: &Conditional) -> ((String, Self), Option<Self>) {
-        // this return type really shows how bad I setup part 1 for part 2
-        // basically, we split the GizmoRange into possibly two GizmoRanges depending
-        // on the condition. One if forward with a new 'key', and the other is an optional residual
-        // (optional since one condition (True) doesn't split)
-        match cond.condition {
-            Condition::X(Operator::LessThan(val)) => (
-                (
-                    cond.result.clone(),
-                    Self {
-                        x: (self.x.0, u32::min(val, self.x.1)),
-                        ..self
-                    },
-                ),
-                Some(Self {
-                    x: (u32::max(val, self.x.0), self.x.1),
-                    ..self
-                }),
-            ),
-            Condition::X(Operator::GreaterThan(val)) => (
-                (
-                    cond.result.clone(),
-                    Self {
-                        x: (u32::max(val + 1, self.x.0), self.x.1),
-                        ..self
-                    },
-                ),
-                Some(Self {
-                    x: (self.x.0, u32::min(val + 1, self.x.1)),
-                    ..self
-                }),
-            ),
-            Condition::M(Operator::LessThan(val)) => (
-                (
-                    cond.result.clone(),
-                    Self {
-                        m: (self.m.0, u32::min(val, self.m.1)),
-                        ..self
-                    },
-                ),
-                Some(Self {
-                    m: (u32::max(val, self.m.0), self.m.1),
-                    ..self
-                }),
-            ),
-            Condition::M(Operator::GreaterThan(val)) => (
-                (
-                    cond.result.clone(),
-                    Self {
-                        m: (u32::max(val + 1, self.m.0), self.m.1),
-                        ..self
-                    },
-                ),
-                Some(Self {
-                    m: (self.m.0, u32::min(val + 1, self.m.1)),
-                    ..self
-                }),
-            ),
-            Condition::A(Operator::LessThan(val)) => (
-                (
-                    cond.result.clone(),
-                    Self {
-                        a: (self.a.0, u32::min(val, self.a.1)),
-                        ..self
-                    },
-                ),
-                Some(Self {
-                    a: (u32::max(val, self.a.0), self.a.1),
-                    ..self
-                }),
-            ),
-            Condition::A(Operator::GreaterThan(val)) => (
-                (
-                    cond.result.clone(),
-                    Self {
-                        a: (u32::max(val + 1, self.a.0), self.a.1),
-                        ..self
-                    },
-                ),
-                Some(Self {
-                    a: (self.a.0, u32::min(val + 1, self.a.1)),
-                    ..self
-                }),
-            ),
-            Condition::S(Operator::LessThan(val)) => (
-                (
-                    cond.result.clone(),
-                    Self {
-                        s: (self.s.0, u32::min(val, self.s.1)),
-                        ..self
-                    },
-                ),
-                Some(Self {
-                    s: (u32::max(val, self.s.0), self.s.1),
-                    ..self
-                }),
-            ),
-            Condition::S(Operator::GreaterThan(val)) => (
-                (
-                    cond.result.clone(),
-                    Self {
-                        s: (u32::max(val + 1, self.s.0), self.s.1),
-                        ..self
-                    },
-                ),
-                Some(Self {
-                    s: (self.s.0, u32::min(val + 1, self.s.1)),
-                    ..self
-                }),
-            ),
-            Condition::True => ((cond.result.clone(), self), None),
+        self.bounds.values().map(|(lo, hi)| hi - lo).product()
+    }
+
+    /// Splits the range into the pieces the condition sends forward to
+    /// `cond.result` and the pieces left over for the next rule
+    fn apply_conditional(&self, cond: &Conditional) -> (Vec<Self>, Vec<Self>) {
+        match &cond.condition {
+            Condition::Compare {
+                attribute,
+                operator,
+                value,
+            } => {
+                let Some(&bounds) = self.bounds.get(attribute) else {
+                    panic!("Range has no attribute {attribute:?}")
+                };
+                let (pass, fail) = operator.split(bounds, *value);
+                let with = |pieces: Vec<Interval>| {
+                    pieces
+                        .into_iter()
+                        .map(|piece| {
+                            let mut range = self.clone();
+                            range.bounds.insert(attribute.clone(), piece);
+                            range
+                        })
+                        .filter(|range| !range.is_empty())
+                        .collect()
+                };
+                (with(pass), with(fail))
+            }
+            Condition::True => (vec![self.clone()], vec![]),
         }
     }
 }
 
-fn day19_p2(data: &str) -> u64 {
-    let (ruleset, _) = data.split_once("\n\n").unwrap();
-    let re = Regex::new(r"([a-zA-Z]+)\{(.+)\}$").unwrap();
-    let ruleset: HashMap<String, Vec<Conditional>> = ruleset
-        .lines()
-        .map(|line| {
-            let captures = re.captures(line.trim()).unwrap();
-            let key = captures[1].to_string();
-            let val = captures[2].split(',').map(Conditional::from).collect();
-            (key, val)
-        })
-        .collect();
-    let mut to_process = vec![("in".to_string(), GizmoRange::new())];
+fn count_accepted(ruleset: &HashMap<String, Vec<Conditional>>, range: GizmoRange) -> u64 {
+    let mut to_process = vec![("in".to_string(), range)];
     let mut valid = vec![];
     while let Some((key, range)) = to_process.pop() {
         let rules = ruleset.get(&key).unwrap();
-        rules.iter().try_fold(range, |left: GizmoRange, rule| {
-            let (new, residual) = left.apply_conditional(rule);
-            if !new.1.is_empty() {
-                if new.0 == "A" {
-                    valid.push(new.1);
-                } else if new.0 != "R" {
-                    to_process.push(new);
+        rules.iter().fold(vec![range], |left, rule| {
+            let mut residual = vec![];
+            for range in left {
+                let (new, rest) = range.apply_conditional(rule);
+                for range in new {
+                    if rule.result == "A" {
+                        valid.push(range);
+                    } else if rule.result != "R" {
+                        to_process.push((rule.result.clone(), range));
+                    }
                 }
+                residual.extend(rest);
             }
             residual
         });
@@ -319,6 +318,212 @@ fn day19_p2(data: &str) -> u64 {
     valid.into_iter().map(|x| x.distinct()).sum()
 }
 
+/// Counts the accepted parts within `range`, which should cover every
+/// attribute the rules look at
+pub fn day19_count_accepted(data: &str, range: GizmoRange) -> u64 {
+    let (ruleset, _) = data.split_once("\n\n").unwrap_or((data, ""));
+    count_accepted(&parse_ruleset(ruleset), range)
+}
+
+fn day19_p2(data: &str) -> u64 {
+    let (ruleset, gizmos) = data.split_once("\n\n").unwrap_or((data, ""));
+    let ruleset = parse_ruleset(ruleset);
+    let range = GizmoRange::new(attribute_names(&ruleset, gizmos), 1, 4000);
+    count_accepted(&ruleset, range)
+}
+
+/// Problems found by statically inspecting a ruleset, without any parts
+#[derive(Debug, Default, PartialEq)]
+pub struct Analysis {
+    /// workflows that can never be reached starting from `in`
+    pub unreachable: Vec<String>,
+    /// (workflow, rule index) of rules that no part can ever match
+    /// because earlier rules in the same workflow already claimed everything
+    pub dead_rules: Vec<(String, usize)>,
+    /// groups of workflows that can send parts around in a loop forever
+    pub cycles: Vec<Vec<String>>,
+    /// (workflow, target) for rules that send parts to a workflow that doesn't exist
+    pub undefined: Vec<(String, String)>,
+    /// (workflow, "A" or "R") for workflows that always end up with the same outcome
+    pub constant: Vec<(String, String)>,
+}
+
+impl Analysis {
+    pub fn is_clean(&self) -> bool {
+        self.unreachable.is_empty()
+            && self.dead_rules.is_empty()
+            && self.cycles.is_empty()
+            && self.undefined.is_empty()
+            && self.constant.is_empty()
+    }
+}
+
+impl std::fmt::Display for Analysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_clean() {
+            return writeln!(f, "No problems found");
+        }
+        for name in &self.unreachable {
+            writeln!(f, "unreachable workflow: {name}")?;
+        }
+        for (name, idx) in &self.dead_rules {
+            writeln!(f, "dead rule: {name}[{idx}]")?;
+        }
+        for cycle in &self.cycles {
+            writeln!(f, "cycle: {}", cycle.join(" -> "))?;
+        }
+        for (name, target) in &self.undefined {
+            writeln!(f, "undefined workflow: {name} -> {target}")?;
+        }
+        for (name, outcome) in &self.constant {
+            writeln!(f, "constant workflow: {name} always {outcome}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Indices of the rules in a workflow that can never match, found by pushing
+/// the full part range through the rules and seeing which get nothing
+fn dead_rules(rules: &[Conditional], full: &GizmoRange) -> Vec<usize> {
+    let mut dead = vec![];
+    let mut left = vec![full.clone()];
+    for (idx, rule) in rules.iter().enumerate() {
+        let mut residual = vec![];
+        let mut matched = false;
+        for range in left {
+            let (new, rest) = range.apply_conditional(rule);
+            matched |= !new.is_empty();
+            residual.extend(rest);
+        }
+        if !matched {
+            dead.push(idx);
+        }
+        left = residual;
+    }
+    dead
+}
+
+/// Strongly connected components of the workflows, only keeping the ones
+/// that actually loop
+fn find_cycles(edges: &HashMap<&str, Vec<&str>>) -> Vec<Vec<String>> {
+    let mut names: Vec<&str> = edges.keys().copied().collect();
+    names.sort();
+    let ids: HashMap<&str, usize> = names
+        .iter()
+        .enumerate()
+        .map(|(ii, &name)| (name, ii))
+        .collect();
+    // edges out to undefined workflows can't be part of a loop
+    let adjacency: Vec<Vec<usize>> = names
+        .iter()
+        .map(|name| {
+            edges[name]
+                .iter()
+                .filter_map(|next| ids.get(next).copied())
+                .collect()
+        })
+        .collect();
+    let mut cycles: Vec<Vec<String>> = strongly_connected_components(&adjacency)
+        .into_iter()
+        .filter(|component| component.len() > 1 || adjacency[component[0]].contains(&component[0]))
+        .map(|component| {
+            component
+                .into_iter()
+                .map(|ii| names[ii].to_string())
+                .collect()
+        })
+        .collect();
+    cycles.sort();
+    cycles
+}
+
+/// Whether a workflow always accepts (Some(true)) or always rejects (Some(false))
+/// regardless of the part. Workflows in cycles or that lead to undefined
+/// workflows are never considered constant.
+fn constant_outcome<'a>(
+    name: &'a str,
+    edges: &HashMap<&'a str, Vec<&'a str>>,
+    memo: &mut HashMap<&'a str, Option<bool>>,
+) -> Option<bool> {
+    match name {
+        "A" => return Some(true),
+        "R" => return Some(false),
+        _ => (),
+    }
+    if let Some(&outcome) = memo.get(name) {
+        return outcome;
+    }
+    let targets = edges.get(name)?;
+    // mark as in progress so that cycles resolve to None
+    memo.insert(name, None);
+    let mut outcomes = targets
+        .iter()
+        .map(|target| constant_outcome(target, edges, memo));
+    let first = outcomes.next().flatten();
+    let outcome = if outcomes.all(|outcome| outcome.is_some() && outcome == first) {
+        first
+    } else {
+        None
+    };
+    memo.insert(name, outcome);
+    outcome
+}
+
+pub fn analyze_ruleset(data: &str) -> Analysis {
+    let (ruleset, gizmos) = data.split_once("\n\n").unwrap_or((data, ""));
+    let ruleset = parse_ruleset(ruleset);
+    let full = GizmoRange::new(attribute_names(&ruleset, gizmos), 1, 4000);
+    let mut analysis = Analysis::default();
+
+    // only rules that can actually match count as edges in the workflow graph
+    let mut edges: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (name, rules) in ruleset.iter() {
+        let dead = dead_rules(rules, &full);
+        analysis
+            .dead_rules
+            .extend(dead.iter().map(|&idx| (name.clone(), idx)));
+        let targets = edges.entry(name.as_str()).or_default();
+        for (idx, rule) in rules.iter().enumerate() {
+            let target = rule.result.as_str();
+            if target != "A" && target != "R" && !ruleset.contains_key(target) {
+                analysis.undefined.push((name.clone(), target.to_string()));
+            }
+            if !dead.contains(&idx) {
+                targets.push(target);
+            }
+        }
+    }
+
+    let mut seen = std::collections::HashSet::new();
+    let mut to_visit = vec!["in"];
+    while let Some(name) = to_visit.pop() {
+        if seen.insert(name) {
+            to_visit.extend(edges.get(name).into_iter().flatten());
+        }
+    }
+    analysis.unreachable = ruleset
+        .keys()
+        .filter(|name| !seen.contains(name.as_str()))
+        .cloned()
+        .collect();
+
+    analysis.cycles = find_cycles(&edges);
+
+    let mut memo = HashMap::new();
+    for name in ruleset.keys() {
+        if let Some(accept) = constant_outcome(name, &edges, &mut memo) {
+            let outcome = if accept { "A" } else { "R" };
+            analysis.constant.push((name.clone(), outcome.to_string()));
+        }
+    }
+
+    analysis.unreachable.sort();
+    analysis.dead_rules.sort();
+    analysis.undefined.sort();
+    analysis.constant.sort();
+    analysis
+}
+
 pub fn run_day19_p1() -> u32 {
     let filename = "data/day_19.txt";
     let data = read_to_string(filename).unwrap();
@@ -331,7 +536,17 @@ pub fn run_day19_p2() -> u64 {
     day19_p2(&data)
 }
 
+pub fn run_day19_analysis() -> Analysis {
+    let filename = "data/day_19.txt";
+    let data = read_to_string(filename).unwrap();
+    analyze_ruleset(&data)
+}
+
 fn main() {
+    if env::args().nth(1).as_deref() == Some("analyze") {
+        print!("{}", run_day19_analysis());
+        return;
+    }
     let part1 = if let Some(arg1) = env::args().nth(1) {
         arg1.parse().unwrap_or(1) == 1
     } else {
@@ -353,6 +568,8 @@ fn main() {
 #[cfg(test)]
 mod test {
     use super::*;
+    use itertools::Itertools;
+    use std::ops::RangeInclusive;
 
     const EXAMPLE: &str = "\
         px{a<2006:qkq,m>2090:A,rfg}\n\
@@ -383,6 +600,154 @@ mod test {
         assert_eq!(day19_p2(EXAMPLE), 167409079868000)
     }
 
+    const GENERIC: &str = "\
+        in{len<=3:small,width==7:R,big}\n\
+        small{width!=2:A,R}\n\
+        big{len>=8:A,width>5:A,R}\n\
+        \n\
+        {len=2,width=5}\n\
+        {len=3,width=2}\n\
+        {len=5,width=7}\n\
+        {len=9,width=1}\n\
+        {len=4,width=6}";
+
+    #[test]
+    fn test_day19_operator_split() {
+        use Operator::*;
+        assert_eq!(LessThan.split((1, 11), 5), (vec![(1, 5)], vec![(5, 11)]));
+        assert_eq!(LessEqual.split((1, 11), 5), (vec![(1, 6)], vec![(6, 11)]));
+        assert_eq!(GreaterThan.split((1, 11), 5), (vec![(6, 11)], vec![(1, 6)]));
+        assert_eq!(
+            GreaterEqual.split((1, 11), 5),
+            (vec![(5, 11)], vec![(1, 5)])
+        );
+        assert_eq!(
+            Equal.split((1, 11), 5),
+            (vec![(5, 6)], vec![(1, 5), (6, 11)])
+        );
+        assert_eq!(
+            NotEqual.split((1, 11), 5),
+            (vec![(1, 5), (6, 11)], vec![(5, 6)])
+        );
+        assert_eq!(Equal.split((1, 11), 20), (vec![], vec![(1, 11)]));
+    }
+
+    #[test]
+    fn test_day19_generic_attributes() {
+        assert_eq!(day19_p1(GENERIC), 2 + 5 + 9 + 1 + 4 + 6);
+        // brute force the same count over a small box
+        let bounds = [("len", 1..=10), ("width", 1..=10)];
+        let range = GizmoRange::new(["len", "width"], 1, 10);
+        assert_eq!(
+            day19_count_accepted(GENERIC, range),
+            brute_force(GENERIC, &bounds)
+        );
+    }
+
+    /// Runs every part in the box through the rules one at a time
+    fn brute_force(data: &str, bounds: &[(&str, RangeInclusive<u32>)]) -> u64 {
+        let ruleset = parse_ruleset(data.split_once("\n\n").unwrap_or((data, "")).0);
+        let parts = bounds
+            .iter()
+            .map(|(name, range)| range.clone().map(move |val| (name.to_string(), val)))
+            .multi_cartesian_product();
+        let mut accepted = 0;
+        for attributes in parts {
+            let gizmo = Gizmo {
+                attributes: attributes.into_iter().collect(),
+            };
+            let mut key = "in".to_string();
+            while key != "A" && key != "R" {
+                key = ruleset[&key].iter().find_map(|r| r.eval(&gizmo)).unwrap();
+            }
+            accepted += (key == "A") as u64;
+        }
+        accepted
+    }
+
+    #[test]
+    fn test_day19_custom_bounds() {
+        // a small box straddling one threshold on each attribute
+        let bounds = [
+            ("x", 1410..=1420),
+            ("m", 1540..=1560),
+            ("a", 2000..=2010),
+            ("s", 1345..=1355),
+        ];
+        let range = bounds.iter().fold(
+            GizmoRange::new(["x", "m", "a", "s"], 1, 4000),
+            |range, (name, vals)| range.with_bounds(name, *vals.start(), *vals.end()),
+        );
+        assert_eq!(
+            day19_count_accepted(EXAMPLE, range),
+            brute_force(EXAMPLE, &bounds)
+        );
+        let range = GizmoRange::new(["x", "m", "a", "s"], 1, 4000);
+        assert_eq!(day19_count_accepted(EXAMPLE, range), 167409079868000);
+    }
+
+    #[test]
+    fn test_day19_bounds_at_type_limit() {
+        let data = "in{len>=4294967294:A,width==4294967295:A,R}";
+        let top = u32::MAX - 3..=u32::MAX;
+        let bounds = [("len", top.clone()), ("width", top)];
+        let range = GizmoRange::new(["len", "width"], u32::MAX - 3, u32::MAX);
+        assert_eq!(day19_count_accepted(data, range), 2 * 4 + 2);
+        assert_eq!(brute_force(data, &bounds), 2 * 4 + 2);
+    }
+
+    #[test]
+    fn test_day19_analysis_example() {
+        let analysis = analyze_ruleset(EXAMPLE);
+        assert!(analysis.unreachable.is_empty());
+        assert!(analysis.dead_rules.is_empty());
+        assert!(analysis.cycles.is_empty());
+        assert!(analysis.undefined.is_empty());
+        assert_eq!(
+            analysis.constant,
+            vec![
+                ("gd".to_string(), "R".to_string()),
+                ("lnx".to_string(), "A".to_string()),
+                ("qs".to_string(), "A".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_day19_analysis_problems() {
+        let ruleset = "\
+            in{x<100:a,x<50:R,b}\n\
+            a{m>10:b,missing}\n\
+            b{s<5:a,A}\n\
+            c{R}\n\
+            d{x>4000:A,a<1:R,A}";
+        let analysis = analyze_ruleset(ruleset);
+        assert_eq!(analysis.unreachable, vec!["c".to_string(), "d".to_string()]);
+        assert_eq!(
+            analysis.dead_rules,
+            vec![
+                ("d".to_string(), 0),
+                ("d".to_string(), 1),
+                ("in".to_string(), 1)
+            ]
+        );
+        assert_eq!(
+            analysis.cycles,
+            vec![vec!["a".to_string(), "b".to_string()]]
+        );
+        assert_eq!(
+            analysis.undefined,
+            vec![("a".to_string(), "missing".to_string())]
+        );
+        assert_eq!(
+            analysis.constant,
+            vec![
+                ("c".to_string(), "R".to_string()),
+                ("d".to_string(), "A".to_string())
+            ]
+        );
+    }
+
     #[test]
     fn test_day19_p1() {
         assert_eq!(run_day19_p1(), 449531);
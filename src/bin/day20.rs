@@ -1,6 +1,7 @@
 //! Day 20: Pulse Propagation
 // Feels like a good day to try out dynamic dispatch
-use num::integer::lcm;
+use aoc_2023::dot;
+use aoc_2023::number_theory::checked_lcm;
 use std::collections::HashMap;
 use std::collections::VecDeque;
 use std::env;
@@ -10,7 +11,7 @@ use std::ops::Not;
 use std::time::Instant;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-enum Pulse {
+pub enum Pulse {
     High,
     Low,
 }
@@ -26,38 +27,71 @@ impl Not for Pulse {
     }
 }
 
-#[derive(Debug, Clone)]
-struct Signal {
-    state: Pulse,
-    src: String,
-    dst: String,
+impl fmt::Display for Pulse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Pulse::High => write!(f, "high"),
+            Pulse::Low => write!(f, "low"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signal {
+    pub state: Pulse,
+    pub src: String,
+    pub dst: String,
 }
 
 impl fmt::Display for Signal {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}{}{}",
-            self.src,
-            match self.state {
-                Pulse::High => " -high-> ",
-                Pulse::Low => " -low-> ",
-            },
-            self.dst
-        )
+        write!(f, "{} -{}-> {}", self.src, self.state, self.dst)
     }
 }
 
-trait Module {
+impl Signal {
+    fn button() -> Self {
+        Signal {
+            state: Pulse::Low,
+            src: "button".to_string(),
+            dst: "broadcaster".to_string(),
+        }
+    }
+}
+
+pub trait Module {
     fn drive_wires(&mut self, sig: Signal) -> Vec<Signal>;
 
     fn in_reset_state(&self) -> bool;
 
     fn add_src(&mut self, _src: String) {}
+
+    fn id(&self) -> &str;
+
+    /// Short human readable name for the kind of module, e.g. "flip-flop"
+    fn kind(&self) -> &'static str;
+
+    fn destinations(&self) -> &[String];
+
+    /// Human readable description of whatever the module remembers between pulses
+    fn state(&self) -> String {
+        String::new()
+    }
+
+    fn send(&self, state: Pulse) -> Vec<Signal> {
+        self.destinations()
+            .iter()
+            .map(|dst| Signal {
+                state,
+                src: self.id().to_string(),
+                dst: dst.clone(),
+            })
+            .collect()
+    }
 }
 
 #[derive(Debug)]
-struct FlipFlop {
+pub struct FlipFlop {
     id: String,
     state: Pulse,
     dst: Vec<String>,
@@ -69,14 +103,7 @@ impl Module for FlipFlop {
             Pulse::High => vec![], // do nothing
             Pulse::Low => {
                 self.state = !self.state;
-                self.dst
-                    .iter()
-                    .map(|dst| Signal {
-                        state: self.state,
-                        src: self.id.clone(),
-                        dst: dst.clone(),
-                    })
-                    .collect()
+                self.send(self.state)
             }
         }
     }
@@ -84,10 +111,29 @@ impl Module for FlipFlop {
     fn in_reset_state(&self) -> bool {
         self.state == Pulse::Low
     }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn kind(&self) -> &'static str {
+        "flip-flop"
+    }
+
+    fn destinations(&self) -> &[String] {
+        &self.dst
+    }
+
+    fn state(&self) -> String {
+        match self.state {
+            Pulse::High => "on".to_string(),
+            Pulse::Low => "off".to_string(),
+        }
+    }
 }
 
 #[derive(Debug)]
-struct Conjunction {
+pub struct Conjunction {
     id: String,
     state: HashMap<String, Pulse>,
     dst: Vec<String>,
@@ -97,23 +143,9 @@ impl Module for Conjunction {
     fn drive_wires(&mut self, sig: Signal) -> Vec<Signal> {
         *self.state.get_mut(&sig.src).expect("Unknown key") = sig.state;
         if self.state.values().all(|&state| state == Pulse::High) {
-            self.dst
-                .iter()
-                .map(|dst| Signal {
-                    state: Pulse::Low,
-                    src: self.id.clone(),
-                    dst: dst.clone(),
-                })
-                .collect()
+            self.send(Pulse::Low)
         } else {
-            self.dst
-                .iter()
-                .map(|dst| Signal {
-                    state: Pulse::High,
-                    src: self.id.clone(),
-                    dst: dst.clone(),
-                })
-                .collect()
+            self.send(Pulse::High)
         }
     }
 
@@ -124,81 +156,221 @@ impl Module for Conjunction {
     fn add_src(&mut self, src: String) {
         self.state.insert(src, Pulse::Low);
     }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn kind(&self) -> &'static str {
+        "conjunction"
+    }
+
+    fn destinations(&self) -> &[String] {
+        &self.dst
+    }
+
+    fn state(&self) -> String {
+        let mut inputs: Vec<_> = self.state.iter().collect();
+        inputs.sort_by_key(|(src, _)| *src);
+        inputs
+            .into_iter()
+            .map(|(src, state)| format!("{src}={state}"))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
 }
 
 #[derive(Debug)]
-struct Broadcaster {
+pub struct Broadcaster {
     dst: Vec<String>,
 }
 
 impl Module for Broadcaster {
     fn drive_wires(&mut self, sig: Signal) -> Vec<Signal> {
-        self.dst
-            .iter()
-            .map(|dst| Signal {
-                state: sig.state,
-                src: "broadcaster".to_string(),
-                dst: dst.clone(),
-            })
-            .collect()
+        self.send(sig.state)
     }
 
     fn in_reset_state(&self) -> bool {
         true
     }
+
+    fn id(&self) -> &str {
+        "broadcaster"
+    }
+
+    fn kind(&self) -> &'static str {
+        "broadcaster"
+    }
+
+    fn destinations(&self) -> &[String] {
+        &self.dst
+    }
+}
+
+/// Sends out the opposite of every pulse it receives
+#[derive(Debug)]
+pub struct Inverter {
+    id: String,
+    dst: Vec<String>,
+}
+
+impl Inverter {
+    pub fn new(id: &str, dst: Vec<String>) -> Self {
+        Self {
+            id: id.to_string(),
+            dst,
+        }
+    }
+}
+
+impl Module for Inverter {
+    fn drive_wires(&mut self, sig: Signal) -> Vec<Signal> {
+        self.send(!sig.state)
+    }
+
+    fn in_reset_state(&self) -> bool {
+        true
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn kind(&self) -> &'static str {
+        "inverter"
+    }
+
+    fn destinations(&self) -> &[String] {
+        &self.dst
+    }
+}
+
+/// Counts low pulses, sending a high pulse on every `modulus`th one and a
+/// low pulse otherwise. High pulses are ignored, like a flip-flop.
+#[derive(Debug)]
+pub struct Counter {
+    id: String,
+    count: usize,
+    modulus: usize,
+    dst: Vec<String>,
+}
+
+impl Counter {
+    pub fn new(id: &str, modulus: usize, dst: Vec<String>) -> Self {
+        assert!(modulus > 0, "Counter modulus must be positive");
+        Self {
+            id: id.to_string(),
+            count: 0,
+            modulus,
+            dst,
+        }
+    }
+}
+
+impl Module for Counter {
+    fn drive_wires(&mut self, sig: Signal) -> Vec<Signal> {
+        match sig.state {
+            Pulse::High => vec![],
+            Pulse::Low => {
+                self.count = (self.count + 1) % self.modulus;
+                if self.count == 0 {
+                    self.send(Pulse::High)
+                } else {
+                    self.send(Pulse::Low)
+                }
+            }
+        }
+    }
+
+    fn in_reset_state(&self) -> bool {
+        self.count == 0
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn kind(&self) -> &'static str {
+        "counter"
+    }
+
+    fn destinations(&self) -> &[String] {
+        &self.dst
+    }
+
+    fn state(&self) -> String {
+        format!("{}/{}", self.count, self.modulus)
+    }
+}
+
+pub type ModuleFactory = Box<dyn Fn(&str, Vec<String>) -> Box<dyn Module>>;
+
+/// The module kinds a circuit description can use, keyed by the prefix
+/// character in front of the module name (e.g. `%` for flip-flops).
+/// `broadcaster` is always available and doesn't need a prefix.
+pub struct ModuleKinds {
+    factories: HashMap<char, ModuleFactory>,
+}
+
+impl Default for ModuleKinds {
+    fn default() -> Self {
+        Self {
+            factories: HashMap::new(),
+        }
+        .register('%', |id, dst| {
+            Box::new(FlipFlop {
+                id: id.to_string(),
+                state: Pulse::Low,
+                dst,
+            })
+        })
+        .register('&', |id, dst| {
+            Box::new(Conjunction {
+                id: id.to_string(),
+                state: HashMap::new(),
+                dst,
+            })
+        })
+    }
+}
+
+impl ModuleKinds {
+    pub fn register(
+        mut self,
+        prefix: char,
+        factory: impl Fn(&str, Vec<String>) -> Box<dyn Module> + 'static,
+    ) -> Self {
+        self.factories.insert(prefix, Box::new(factory));
+        self
+    }
 }
 
-fn initialize_system(data: &str) -> (HashMap<String, Box<dyn Module>>, String) {
+type System = (HashMap<String, Box<dyn Module>>, String);
+
+fn initialize_system(data: &str, kinds: &ModuleKinds) -> System {
     let mut sources: HashMap<String, Vec<String>> = HashMap::new();
     let mut modules: HashMap<String, Box<dyn Module>> = data
         .lines()
         .map(|line| {
             let (ident, dest) = line.split_once(" -> ").unwrap();
             let dest: Vec<String> = dest.split(',').map(|x| x.trim().to_owned()).collect();
-            if let Some(rest) = ident.strip_prefix('%') {
-                dest.iter().for_each(|dst| {
-                    sources
-                        .entry(dst.to_string())
-                        .or_insert(Vec::new())
-                        .push(rest.to_string())
-                });
-                (
-                    rest.to_string(),
-                    Box::new(FlipFlop {
-                        id: rest.to_string(),
-                        state: Pulse::Low,
-                        dst: dest,
-                    }) as Box<dyn Module>,
-                )
-            } else if let Some(rest) = ident.strip_prefix('&') {
-                dest.iter().for_each(|dst| {
-                    sources
-                        .entry(dst.to_string())
-                        .or_insert(Vec::new())
-                        .push(rest.to_string())
-                });
-                (
-                    rest.to_string(),
-                    Box::new(Conjunction {
-                        id: rest.to_string(),
-                        state: HashMap::new(),
-                        dst: dest,
-                    }) as Box<dyn Module>,
-                )
-            } else if let Some(_rest) = ident.strip_prefix("broadcaster") {
-                dest.iter().for_each(|dst| {
-                    sources
-                        .entry(dst.to_string())
-                        .or_insert(Vec::new())
-                        .push("broadcaster".to_string())
-                });
-                (
-                    "broadcaster".to_string(),
-                    Box::new(Broadcaster { dst: dest }) as Box<dyn Module>,
-                )
+            let modl = if ident == "broadcaster" {
+                Box::new(Broadcaster { dst: dest }) as Box<dyn Module>
             } else {
-                panic!("Could not parse {ident:?}");
-            }
+                let mut chars = ident.chars();
+                let prefix = chars.next().unwrap();
+                match kinds.factories.get(&prefix) {
+                    Some(factory) => factory(chars.as_str(), dest),
+                    None => panic!("Could not parse {ident:?}"),
+                }
+            };
+            modl.destinations().iter().for_each(|dst| {
+                sources
+                    .entry(dst.to_string())
+                    .or_default()
+                    .push(modl.id().to_string())
+            });
+            (modl.id().to_string(), modl)
         })
         .collect();
     let mut output = "".to_string();
@@ -215,31 +387,172 @@ fn initialize_system(data: &str) -> (HashMap<String, Box<dyn Module>>, String) {
     (modules, output)
 }
 
+/// A pulse circuit that can be run one button press or one pulse at a time
+pub struct Circuit {
+    modules: HashMap<String, Box<dyn Module>>,
+    output: String,
+    queue: VecDeque<Signal>,
+    presses: usize,
+}
+
+impl Circuit {
+    pub fn new(data: &str) -> Self {
+        Self::with_kinds(data, &ModuleKinds::default())
+    }
+
+    pub fn with_kinds(data: &str, kinds: &ModuleKinds) -> Self {
+        let (modules, output) = initialize_system(data, kinds);
+        Self {
+            modules,
+            output,
+            queue: VecDeque::new(),
+            presses: 0,
+        }
+    }
+
+    /// Delivers the next pulse in flight, pressing the button first if
+    /// nothing is in flight. Returns the pulse that was delivered.
+    pub fn step(&mut self) -> Signal {
+        if self.queue.is_empty() {
+            self.queue.push_back(Signal::button());
+            self.presses += 1;
+        }
+        let signal = self.queue.pop_front().unwrap();
+        // pulses to the output (or any other untyped module) go nowhere
+        if let Some(modl) = self.modules.get_mut(&signal.dst) {
+            self.queue.extend(modl.drive_wires(signal.clone()));
+        }
+        signal
+    }
+
+    /// Presses the button and runs until every pulse has been delivered,
+    /// returning them all in order. If a press was left half finished by
+    /// [`Circuit::step`], that press is completed instead.
+    pub fn press_button(&mut self) -> Vec<Signal> {
+        let mut trace = vec![self.step()];
+        while !self.queue.is_empty() {
+            trace.push(self.step());
+        }
+        trace
+    }
+
+    pub fn presses(&self) -> usize {
+        self.presses
+    }
+
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// Ids of every module with a wire leading to `id`, in sorted order
+    pub fn sources(&self, id: &str) -> Vec<&str> {
+        let mut sources: Vec<&str> = self
+            .modules
+            .values()
+            .filter(|modl| modl.destinations().iter().any(|dst| dst == id))
+            .map(|modl| modl.id())
+            .collect();
+        sources.sort();
+        sources
+    }
+
+    pub fn module(&self, id: &str) -> Option<&dyn Module> {
+        self.modules.get(id).map(|modl| modl.as_ref())
+    }
+
+    pub fn modules(&self) -> impl Iterator<Item = &dyn Module> {
+        self.modules.values().map(|modl| modl.as_ref())
+    }
+
+    pub fn in_reset_state(&self) -> bool {
+        self.queue.is_empty() && self.modules.values().all(|modl| modl.in_reset_state())
+    }
+}
+
+/// Number of (low, high) pulses sent along each (src, dst) wire
+pub type PulseCounts = HashMap<(String, String), (u64, u64)>;
+
+impl Circuit {
+    /// Presses the button `presses` times, tallying every pulse sent
+    pub fn count_pulses(&mut self, presses: usize) -> PulseCounts {
+        let mut counts: PulseCounts = HashMap::new();
+        for _ in 0..presses {
+            for signal in self.press_button() {
+                let count = counts.entry((signal.src, signal.dst)).or_default();
+                match signal.state {
+                    Pulse::Low => count.0 += 1,
+                    Pulse::High => count.1 += 1,
+                }
+            }
+        }
+        counts
+    }
+
+    /// Graphviz DOT of the circuit, optionally labelling each wire
+    /// with the pulses that went along it (see [`Circuit::count_pulses`])
+    pub fn to_dot(&self, counts: Option<&PulseCounts>) -> dot::Graph {
+        let mut graph = dot::Graph::directed("circuit");
+        let mut ids: Vec<&String> = self.modules.keys().collect();
+        ids.sort();
+        for id in ids.iter() {
+            let style: &[(&str, &str)] = match self.modules[*id].kind() {
+                "flip-flop" => &[("shape", "box")],
+                "conjunction" => &[("shape", "diamond")],
+                "broadcaster" => &[("shape", "doubleoctagon")],
+                _ => &[("shape", "ellipse")],
+            };
+            graph.node(id, style);
+        }
+        graph.node(
+            &self.output,
+            &[("style", "filled"), ("fillcolor", "tomato")],
+        );
+        for id in ids {
+            for dst in self.modules[id].destinations() {
+                match counts.and_then(|counts| counts.get(&(id.clone(), dst.clone()))) {
+                    Some((low, high)) => {
+                        let label = format!("{low} low / {high} high");
+                        graph.edge(id, dst, &[("label", &label)])
+                    }
+                    None => graph.edge(id, dst, &[]),
+                }
+            }
+        }
+        graph
+    }
+}
+
+impl fmt::Display for Circuit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut ids: Vec<&String> = self.modules.keys().collect();
+        ids.sort();
+        for id in ids {
+            let modl = &self.modules[id];
+            write!(f, "{id} ({})", modl.kind())?;
+            let state = modl.state();
+            if !state.is_empty() {
+                write!(f, " [{state}]")?;
+            }
+            writeln!(f, " -> {}", modl.destinations().join(", "))?;
+        }
+        for signal in &self.queue {
+            writeln!(f, "in flight: {signal}")?;
+        }
+        Ok(())
+    }
+}
+
 fn day20_p1(data: &str) -> u32 {
-    let (mut modules, output) = initialize_system(data);
+    let mut circuit = Circuit::new(data);
     let mut pulse_counts = vec![];
-    while pulse_counts.is_empty() || modules.values().any(|modl| !modl.in_reset_state()) {
+    while pulse_counts.is_empty() || !circuit.in_reset_state() {
         let mut pulse_count = (0, 0);
-        let mut queue = VecDeque::new();
-        queue.push_back(Signal {
-            state: Pulse::Low,
-            src: "button".to_string(),
-            dst: "broadcaster".to_string(),
-        });
-        while let Some(signal) = queue.pop_front() {
+        for signal in circuit.press_button() {
             // println!("{signal}");
             match signal.state {
                 Pulse::Low => pulse_count.0 += 1,
                 Pulse::High => pulse_count.1 += 1,
             }
-            if signal.dst != output {
-                modules
-                    .get_mut(&signal.dst)
-                    .unwrap()
-                    .drive_wires(signal)
-                    .into_iter()
-                    .for_each(|signal| queue.push_back(signal));
-            }
         }
         // dbg!(&pulse_count);
         pulse_counts.push(pulse_count);
@@ -257,53 +570,137 @@ fn day20_p1(data: &str) -> u32 {
     low_count * high_count
 }
 
-fn day20_p2(data: &str) -> u64 {
-    // this took some data inspection. The output "rx" is driven
-    // by a conjunction module "xm". "xm" only goes low when all four
-    // of it's inputs go high in the same cycle. Each of "xm"'s inputs
-    // are independently driven. We find the rate at which each one
-    // turns on and then find the least common multiple of the cycles
-    // to find how many cycle it would take to get them all to line up.
-    let (mut modules, _) = initialize_system(data);
-    let output = "xm".to_string();
-    let mut cycle_count = 0;
-    let mut xm_in_cycles = [0; 4];
-    while cycle_count == 0 || modules.values().any(|modl| !modl.in_reset_state()) {
-        let mut queue = VecDeque::new();
-        queue.push_back(Signal {
-            state: Pulse::Low,
-            src: "button".to_string(),
-            dst: "broadcaster".to_string(),
-        });
-        cycle_count += 1;
-        while let Some(signal) = queue.pop_front() {
-            // println!("{}", signal.to_string());
-            if signal.dst != output {
-                modules
-                    .get_mut(&signal.dst)
-                    .unwrap()
-                    .drive_wires(signal)
-                    .into_iter()
-                    .for_each(|signal| queue.push_back(signal));
-            } else {
-                let i = match &signal.src[..] {
-                    "ft" => 0,
-                    "jz" => 1,
-                    "ng" => 2,
-                    "sv" => 3,
-                    _ => unreachable!(),
-                };
-                match signal.state {
-                    Pulse::Low => (),
-                    Pulse::High => xm_in_cycles[i] = cycle_count,
+/// Upper limit on button presses spent measuring each sub-circuit's period
+const MAX_PRESSES: usize = 1 << 16;
+
+/// How many high pulses each sub-circuit has to line up on before its period
+/// is trusted
+const PERIODS_CHECKED: usize = 3;
+
+/// The period of an input that went high on `presses`, provided every one of
+/// them is a whole multiple of the first
+fn check_period(input: &str, presses: &[u64]) -> Result<u64, String> {
+    let first = presses[0];
+    for (nth, &press) in (1..).zip(presses) {
+        if press != nth * first {
+            return Err(format!(
+                "{input:?} goes high on presses {}, \
+                 so it isn't periodic from the start",
+                presses
+                    .iter()
+                    .map(u64::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+    }
+    Ok(first)
+}
+
+/// Finds how many button presses it takes before the output gets a low pulse.
+///
+/// Brute force is hopeless, so this relies on the circuit having a specific
+/// structure: the output is driven by a single conjunction, and each input of
+/// that conjunction is driven by its own independent sub-circuit that sends a
+/// high pulse once every `period` presses (first on press `period`). The
+/// conjunction goes low when all of them line up, which is the LCM of the periods.
+/// An error describes which part of that assumption doesn't hold.
+pub fn presses_until_output_low(mut circuit: Circuit) -> Result<u64, String> {
+    let output = circuit.output().to_string();
+    let feeders = circuit.sources(&output);
+    let [join] = feeders[..] else {
+        return Err(format!(
+            "output {output:?} is driven by {} modules, expected one conjunction",
+            feeders.len()
+        ));
+    };
+    let join = join.to_string();
+    let kind = circuit.module(&join).unwrap().kind();
+    if kind != "conjunction" {
+        return Err(format!(
+            "output {output:?} is driven by {join:?}, a {kind}, not a conjunction"
+        ));
+    }
+    let inputs: Vec<String> = circuit
+        .sources(&join)
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+
+    // every input must be driven by a disjoint set of modules, starting from the broadcaster
+    let mut owner: HashMap<String, &str> = HashMap::new();
+    for input in inputs.iter() {
+        let mut to_visit = vec![input.as_str()];
+        let mut seen = std::collections::HashSet::new();
+        while let Some(id) = to_visit.pop() {
+            if id == "broadcaster" || !seen.insert(id) {
+                continue;
+            }
+            if id == join {
+                return Err(format!("{join:?} feeds back into its own input {input:?}"));
+            }
+            if let Some(other) = owner.insert(id.to_string(), input) {
+                return Err(format!(
+                    "sub-circuits driving {other:?} and {input:?} share module {id:?}"
+                ));
+            }
+            to_visit.extend(circuit.sources(id));
+        }
+    }
+
+    let mut highs: HashMap<&str, Vec<u64>> = HashMap::new();
+    while inputs
+        .iter()
+        .any(|input| highs.get(input.as_str()).map_or(0, Vec::len) < PERIODS_CHECKED)
+    {
+        if circuit.presses() >= MAX_PRESSES {
+            return Err(format!(
+                "not every input of {join:?} went high {PERIODS_CHECKED} times \
+                 within {MAX_PRESSES} presses"
+            ));
+        }
+        let mut last = HashMap::new();
+        for signal in circuit.press_button() {
+            if signal.dst != join {
+                continue;
+            }
+            let input = inputs.iter().find(|&input| *input == signal.src).unwrap();
+            last.insert(input.as_str(), signal.state);
+            if signal.state == Pulse::High {
+                let presses = highs.entry(input).or_default();
+                // several high pulses in one press only count once
+                if presses.last() != Some(&(circuit.presses() as u64)) {
+                    presses.push(circuit.presses() as u64);
                 }
             }
         }
-        if xm_in_cycles.iter().all(|&x| x != 0) {
-            break;
+        // an input left high could line up with the others on a later press
+        if let Some((input, _)) = last.iter().find(|(_, &state)| state == Pulse::High) {
+            return Err(format!(
+                "{input:?} is still high at the end of press {}",
+                circuit.presses()
+            ));
         }
     }
-    xm_in_cycles.into_iter().fold(1, lcm)
+
+    inputs
+        .iter()
+        .try_fold(1, |acc, input| {
+            let period = check_period(input, &highs[input.as_str()])?;
+            checked_lcm(acc, period as i128).ok_or("the periods' LCM overflows".to_string())
+        })
+        .and_then(|presses| u64::try_from(presses).map_err(|_| "too many presses".to_string()))
+}
+
+fn day20_p2(data: &str) -> u64 {
+    // this took some data inspection, which has since been automated.
+    // In the puzzle input "rx" is driven by the conjunction "xm", which
+    // only goes low when all four of its independently driven inputs go
+    // high in the same cycle.
+    match presses_until_output_low(Circuit::new(data)) {
+        Ok(presses) => presses,
+        Err(err) => panic!("Circuit does not decompose: {err}"),
+    }
 }
 
 pub fn run_day20_p1() -> u32 {
@@ -319,6 +716,15 @@ pub fn run_day20_p2() -> u64 {
 }
 
 fn main() {
+    if env::args().nth(1).as_deref() == Some("dot") {
+        // `day20 dot [presses]` to annotate the wires with pulse counts
+        let data = read_to_string("data/day_20.txt").unwrap();
+        let mut circuit = Circuit::new(&data);
+        let presses = env::args().nth(2).map(|arg| arg.parse().unwrap());
+        let counts = presses.map(|presses| circuit.count_pulses(presses));
+        print!("{}", circuit.to_dot(counts.as_ref()));
+        return;
+    }
     let part1 = if let Some(arg1) = env::args().nth(1) {
         arg1.parse().unwrap_or(1) == 1
     } else {
@@ -353,6 +759,175 @@ mod test {
         assert_eq!(day20_p1(EXAMPLE), 11687500);
     }
 
+    const EXAMPLE_SIMPLE: &str = "\
+        broadcaster -> a, b, c\n\
+        %a -> b\n\
+        %b -> c\n\
+        %c -> inv\n\
+        &inv -> a";
+
+    #[test]
+    fn test_day20_p1_example_simple() {
+        assert_eq!(day20_p1(EXAMPLE_SIMPLE), 32000000);
+    }
+
+    #[test]
+    fn test_day20_press_trace() {
+        let mut circuit = Circuit::new(EXAMPLE_SIMPLE);
+        let trace: Vec<String> = circuit
+            .press_button()
+            .iter()
+            .map(|signal| signal.to_string())
+            .collect();
+        assert_eq!(
+            trace,
+            vec![
+                "button -low-> broadcaster",
+                "broadcaster -low-> a",
+                "broadcaster -low-> b",
+                "broadcaster -low-> c",
+                "a -high-> b",
+                "b -high-> c",
+                "c -high-> inv",
+                "inv -low-> a",
+                "a -low-> b",
+                "b -low-> c",
+                "c -low-> inv",
+                "inv -high-> a",
+            ]
+        );
+        assert!(circuit.in_reset_state());
+        assert_eq!(circuit.presses(), 1);
+    }
+
+    #[test]
+    fn test_day20_step_and_state() {
+        let mut circuit = Circuit::new(EXAMPLE);
+        assert_eq!(circuit.output(), "output");
+        assert_eq!(circuit.step().to_string(), "button -low-> broadcaster");
+        assert_eq!(circuit.step().to_string(), "broadcaster -low-> a");
+        assert_eq!(circuit.module("a").unwrap().state(), "on");
+        assert_eq!(
+            circuit.to_string(),
+            "\
+            a (flip-flop) [on] -> inv, con\n\
+            b (flip-flop) [off] -> con\n\
+            broadcaster (broadcaster) -> a\n\
+            con (conjunction) [a=low b=low] -> output\n\
+            inv (conjunction) [a=low] -> b\n\
+            in flight: a -high-> inv\n\
+            in flight: a -high-> con\n"
+        );
+        // finishes the press that was already underway
+        assert_eq!(circuit.press_button().len(), 6);
+        assert_eq!(circuit.presses(), 1);
+        assert_eq!(circuit.module("con").unwrap().state(), "a=high b=high");
+    }
+
+    #[test]
+    fn test_day20_custom_modules() {
+        let kinds = ModuleKinds::default()
+            .register('!', |id, dst| Box::new(Inverter::new(id, dst)))
+            .register('#', |id, dst| Box::new(Counter::new(id, 3, dst)));
+        let mut circuit = Circuit::with_kinds("broadcaster -> c\n#c -> n\n!n -> out", &kinds);
+        assert_eq!(circuit.module("n").unwrap().kind(), "inverter");
+        let to_out = |trace: Vec<Signal>| trace.into_iter().find(|s| s.dst == "out").unwrap();
+        assert_eq!(to_out(circuit.press_button()).state, Pulse::High);
+        assert_eq!(circuit.module("c").unwrap().state(), "1/3");
+        assert_eq!(to_out(circuit.press_button()).state, Pulse::High);
+        assert_eq!(to_out(circuit.press_button()).state, Pulse::Low);
+        assert!(circuit.in_reset_state());
+    }
+
+    fn counter_kinds() -> ModuleKinds {
+        ModuleKinds::default()
+            .register('3', |id, dst| Box::new(Counter::new(id, 3, dst)))
+            .register('4', |id, dst| Box::new(Counter::new(id, 4, dst)))
+    }
+
+    #[test]
+    fn test_day20_structural_analysis() {
+        // two flip-flop counters that reset themselves every 5 and 7 presses,
+        // built the same way as the puzzle input
+        let data = "\
+            broadcaster -> a0, b0\n\
+            %a0 -> a1, ac\n\
+            %a1 -> a2\n\
+            %a2 -> ac\n\
+            &ac -> a0, a1, ai\n\
+            &ai -> join\n\
+            %b0 -> b1, bc\n\
+            %b1 -> b2, bc\n\
+            %b2 -> bc\n\
+            &bc -> b0, bi\n\
+            &bi -> join\n\
+            &join -> rx";
+        assert_eq!(day20_p2(data), 35);
+        // and check it against brute force
+        let mut circuit = Circuit::new(data);
+        while !circuit
+            .press_button()
+            .iter()
+            .any(|signal| signal.dst == "rx" && signal.state == Pulse::Low)
+        {}
+        assert_eq!(circuit.presses(), 35);
+    }
+
+    #[test]
+    fn test_day20_structural_analysis_errors() {
+        let kinds = counter_kinds();
+        let analyze = |data| presses_until_output_low(Circuit::with_kinds(data, &kinds));
+        assert!(analyze("broadcaster -> a\n3a -> rx")
+            .unwrap_err()
+            .contains("not a conjunction"));
+        assert!(analyze("broadcaster -> a, b\n3a -> rx\n4b -> rx")
+            .unwrap_err()
+            .contains("2 modules"));
+        let shared = "broadcaster -> a\n3a -> x, y\n&x -> join\n&y -> join\n&join -> rx";
+        assert!(analyze(shared).unwrap_err().contains("share module \"a\""));
+        // a counter that only resets its top bit, so it hits 5 and then every 4 presses
+        let offset = "broadcaster -> a0\n\
+            %a0 -> a1, ac\n\
+            %a1 -> a2\n\
+            %a2 -> ac\n\
+            &ac -> a2, ai\n\
+            &ai -> join\n\
+            &join -> rx";
+        assert!(analyze(offset).unwrap_err().contains("presses 5, 9, 13"));
+        // periodic, but a counter holds its high pulse until the next press
+        let lingering = "broadcaster -> a, b\n3a -> join\n4b -> join\n&join -> rx";
+        assert!(analyze(lingering).unwrap_err().contains("still high"));
+    }
+
+    #[test]
+    fn test_day20_check_period() {
+        assert_eq!(check_period("a", &[3, 6, 9]), Ok(3));
+        assert_eq!(check_period("a", &[3, 6, 9, 12]), Ok(3));
+        // lines up twice by chance, but drifts on the next period
+        let err = check_period("a", &[2, 4, 5]).unwrap_err();
+        assert!(err.contains("presses 2, 4, 5"));
+        assert!(check_period("a", &[5, 9, 13]).is_err());
+    }
+
+    #[test]
+    fn test_day20_dot() {
+        let mut circuit = Circuit::new(EXAMPLE);
+        let dot = circuit.to_dot(None).to_string();
+        assert!(dot.contains("\"a\" [shape=\"box\"];"));
+        assert!(dot.contains("\"con\" [shape=\"diamond\"];"));
+        assert!(dot.contains("\"broadcaster\" [shape=\"doubleoctagon\"];"));
+        assert!(dot.contains("\"output\" [style=\"filled\", fillcolor=\"tomato\"];"));
+        assert!(dot.contains("\"a\" -> \"inv\";"));
+        let counts = circuit.count_pulses(4);
+        assert_eq!(
+            counts[&("button".to_string(), "broadcaster".to_string())],
+            (4, 0)
+        );
+        let dot = circuit.to_dot(Some(&counts)).to_string();
+        assert!(dot.contains("\"broadcaster\" -> \"a\" [label=\"4 low / 0 high\"];"));
+        assert!(dot.contains("\"a\" -> \"inv\" [label=\"2 low / 2 high\"];"));
+    }
+
     #[test]
     fn test_day20_p1() {
         assert_eq!(run_day20_p1(), 788081152);
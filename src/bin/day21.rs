@@ -2,8 +2,10 @@
 #![allow(dead_code)]
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 use std::env;
 use std::fs::read_to_string;
+use std::iter::successors;
 use std::time::Instant;
 
 // Coordinates for convenience. It encapsulates boundary conditions
@@ -249,13 +251,18 @@ impl<const N: usize> From<Coordinate<N>> for WrappingCoordinate<N> {
     }
 }
 
-fn day21_p2<const N: usize, const STEPS: usize>(data: &str) -> u64 {
-    let (chart, start) = get_state::<N>(data);
+/// Distances of every plot within `max_steps` of the start, out across the
+/// repeating tiles of the garden
+fn wrapping_distances<const N: usize>(
+    chart: &[[Tile; N]; N],
+    start: Coordinate<N>,
+    max_steps: usize,
+) -> Vec<usize> {
     let start: WrappingCoordinate<N> = start.into();
 
     let mut dist_map: HashMap<WrappingCoordinate<N>, usize> = HashMap::new();
     dist_map.insert(start, 0);
-    (1..STEPS + 1).fold(HashSet::from([start]), |prev, step| {
+    (1..max_steps + 1).fold(HashSet::from([start]), |prev, step| {
         let mut next = HashSet::new();
         for coord in prev.into_iter() {
             let neighbors = [coord.up(), coord.down(), coord.right(), coord.left()];
@@ -270,31 +277,265 @@ fn day21_p2<const N: usize, const STEPS: usize>(data: &str) -> u64 {
         }
         next
     });
-    let parity = STEPS % 2;
-    dist_map.into_values().filter(|x| x % 2 == parity).count() as u64
+    dist_map.into_values().collect()
+}
+
+/// Plots that can be ended on after exactly `steps`, given the distances
+/// to them. Any plot closer by an even number of steps can be reached by
+/// stepping back and forth.
+fn count_reachable(distances: &[usize], steps: usize) -> u64 {
+    distances
+        .iter()
+        .filter(|&&dist| dist <= steps && dist % 2 == steps % 2)
+        .count() as u64
+}
+
+fn day21_p2<const N: usize, const STEPS: usize>(data: &str) -> u64 {
+    let (chart, start) = get_state::<N>(data);
+    count_reachable(&wrapping_distances(&chart, start, STEPS), STEPS)
+}
+
+/// Whether the start is in the middle of clear rows and columns, with a
+/// clear border around the tile. Then the reachable plots spread out as a
+/// diamond that gains a fixed pattern of whole tiles every `N` steps,
+/// making the count quadratic in the number of tiles crossed.
+fn has_clear_lanes<const N: usize>(chart: &[[Tile; N]; N], start: Coordinate<N>) -> bool {
+    let clear_row = |row: usize| chart[row].iter().all(|&tile| tile == Tile::Garden);
+    let clear_col = |col: usize| chart.iter().all(|line| line[col] == Tile::Garden);
+    start == Coordinate::from((N / 2, N / 2))
+        && [0, N / 2, N - 1]
+            .into_iter()
+            .all(|ii| clear_row(ii) && clear_col(ii))
+}
+
+/// Shortest distances from a set of starts out across the infinite garden,
+/// found by BFS over a block of `(2 * radius + 1)^2` tiles. Copies of a plot
+/// past the block are counted without visiting them, on the assumption that
+/// every tile further out adds exactly `N` to the distance: a line of them
+/// straight out from each edge tile of the block, and a growing triangle of
+/// them out from each corner tile. That holds once the outer ring of tiles
+/// is exactly `N` further than the ring inside it, which is checked, and the
+/// block doubles in size until it does, up to `N` tiles out. Only how many
+/// copies are at each distance matters, so that's all that is kept.
+#[derive(Debug, Clone, PartialEq)]
+struct DistanceCounts<const N: usize> {
+    /// copies of plots in the block at each distance
+    block: Vec<u64>,
+    /// the same, just for the tiles along the edge of the block but not
+    /// in its corners
+    edges: Vec<u64>,
+    /// and just for its corner tiles
+    corners: Vec<u64>,
+}
+
+impl<const N: usize> DistanceCounts<N> {
+    const TILE_RADIUS: usize = 3;
+
+    fn new(chart: &[[Tile; N]; N], starts: &[Coordinate<N>]) -> Result<Self, String> {
+        let max_radius = N.max(Self::TILE_RADIUS);
+        let (radius, dist) = successors(Some(Self::TILE_RADIUS), |&radius| {
+            (radius < max_radius).then(|| (2 * radius).min(max_radius))
+        })
+        .map(|radius| (radius, Self::block_distances(chart, starts, radius)))
+        .find(|(radius, dist)| Self::repeats_outward(dist, *radius))
+        .ok_or(format!(
+            "Garden distances don't settle into repeating every tile within {max_radius} tiles"
+        ))?;
+        let width = (2 * radius + 1) * N;
+        let furthest = dist.iter().filter(|&&d| d != usize::MAX).max().unwrap();
+        let mut counts = Self {
+            block: vec![0; furthest + 1],
+            edges: vec![0; furthest + 1],
+            corners: vec![0; furthest + 1],
+        };
+        let last = 2 * radius;
+        for (pos, &d) in dist.iter().enumerate() {
+            if d == usize::MAX {
+                continue;
+            }
+            counts.block[d] += 1;
+            let (tile_row, tile_col) = (pos / width / N, pos % width / N);
+            match (
+                tile_row == 0 || tile_row == last,
+                tile_col == 0 || tile_col == last,
+            ) {
+                (true, true) => counts.corners[d] += 1,
+                (true, false) | (false, true) => counts.edges[d] += 1,
+                _ => {}
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Multi-source BFS over the block, with the starts in its middle tile
+    fn block_distances(
+        chart: &[[Tile; N]; N],
+        starts: &[Coordinate<N>],
+        radius: usize,
+    ) -> Vec<usize> {
+        let width = (2 * radius + 1) * N;
+        let mut dist = vec![usize::MAX; width * width];
+        let mut queue = VecDeque::new();
+        for start in starts {
+            let origin = (radius * N + start.row) * width + radius * N + start.col;
+            dist[origin] = 0;
+            queue.push_back(origin);
+        }
+        while let Some(pos) = queue.pop_front() {
+            let (row, col) = (pos / width, pos % width);
+            let neighbors = [
+                (row > 0).then(|| pos - width),
+                (row < width - 1).then(|| pos + width),
+                (col > 0).then(|| pos - 1),
+                (col < width - 1).then(|| pos + 1),
+            ];
+            for next in neighbors.into_iter().flatten() {
+                let (r, c) = (next / width, next % width);
+                if dist[next] == usize::MAX && chart[r % N][c % N] != Tile::Rock {
+                    dist[next] = dist[pos] + 1;
+                    queue.push_back(next);
+                }
+            }
+        }
+        dist
+    }
+
+    /// Whether every plot in the outer ring of tiles is exactly `N` further
+    /// than its copy one tile in, along each direction the ring faces out
+    fn repeats_outward(dist: &[usize], radius: usize) -> bool {
+        let width = (2 * radius + 1) * N;
+        let last = 2 * radius;
+        dist.iter().enumerate().all(|(pos, &d)| {
+            let (tile_row, tile_col) = (pos / width / N, pos % width / N);
+            let inward = [
+                (tile_row == 0).then(|| pos + N * width),
+                (tile_row == last).then(|| pos - N * width),
+                (tile_col == 0).then(|| pos + N),
+                (tile_col == last).then(|| pos - N),
+            ];
+            inward
+                .into_iter()
+                .flatten()
+                .all(|inner| match (d, dist[inner]) {
+                    (usize::MAX, usize::MAX) => true,
+                    (usize::MAX, _) | (_, usize::MAX) => false,
+                    (d, inner) => d == inner + N,
+                })
+        })
+    }
+
+    /// Copies of plots at most `steps` away, and if `parity` is set only
+    /// those an even number of steps short of it
+    fn count(&self, steps: usize, parity: bool) -> u64 {
+        (0..self.block.len().min(steps + 1))
+            .map(|d| {
+                let mut count = 0;
+                if !parity || d % 2 == steps % 2 {
+                    count += self.block[d];
+                }
+                count += self.edges[d] * tiles_beyond::<N>(steps - d, false, parity);
+                count + self.corners[d] * tiles_beyond::<N>(steps - d, true, parity)
+            })
+            .sum()
+    }
+}
+
+/// Reachability across the infinite garden from one or more starts.
+///
+/// Stepping back and forth means a plot can be ended on after exactly
+/// `steps` when some start is no further than that by a path of the same
+/// parity. The garden is a grid, so that parity only depends on the colour
+/// of the start on a checkerboard, which is why distances are kept
+/// separately for the starts of each colour. For a given `steps` a plot can
+/// then only be counted through one colour, so the counts just add up.
+#[derive(Debug, Clone, PartialEq)]
+struct GardenDistances<const N: usize> {
+    /// distances to the nearest start of each colour that has any
+    by_colour: Vec<DistanceCounts<N>>,
+    /// distances to the nearest start of any colour
+    nearest: DistanceCounts<N>,
+}
+
+impl<const N: usize> GardenDistances<N> {
+    /// Distances from `starts`, all in the original tile
+    fn new(chart: &[[Tile; N]; N], starts: &[Coordinate<N>]) -> Result<Self, String> {
+        let (even, odd): (Vec<_>, Vec<_>) = starts
+            .iter()
+            .partition(|start| (start.row + start.col) % 2 == 0);
+        let by_colour: Vec<DistanceCounts<N>> = [even, odd]
+            .into_iter()
+            .filter(|starts| !starts.is_empty())
+            .map(|starts| DistanceCounts::new(chart, &starts))
+            .collect::<Result<_, _>>()?;
+        let nearest = match &by_colour[..] {
+            [only] => only.clone(),
+            _ => DistanceCounts::new(chart, starts)?,
+        };
+        Ok(Self { by_colour, nearest })
+    }
+
+    /// Plots that can be ended on after exactly `steps`
+    fn exactly(&self, steps: usize) -> u64 {
+        self.by_colour
+            .iter()
+            .map(|counts| counts.count(steps, true))
+            .sum()
+    }
+
+    /// Plots that can be reached in at most `steps`
+    fn within(&self, steps: usize) -> u64 {
+        self.nearest.count(steps, false)
+    }
 }
 
-fn day21_p2_v2(_data: &str) -> u64 {
-    // this solution annoys me because it doesn't necessarily generalize
-    // I had to inspect the very specific case I was given an construct example for it.
-    // Maybe I can come back later and make it more general.
-    const N: usize = 131;
-    const STEPS: usize = 26_501_365;
-    let l1_radius: usize = (STEPS - N / 2) / N;
-    // let l1_rem: usize = (STEPS - N / 2) % N; // =0
-    // dbg!(l1_radius, l1_rem);
-    // So I chose these STEP counts to product examples that would
-    // be equivalent but smaller (a lot of data inspection behind equivalent here).
-    // I then did a polynomial fit to the data and it had a perfect quadratic fit.
-    // dbg!( // the x below is even, so are my test points
-    //     day21_p2::<131, 327>(&data),  // 2
-    //     day21_p2::<131, 589>(&data),  // 4
-    //     day21_p2::<131, 851>(&data),  // 6
-    //     day21_p2::<131, 1113>(&data), // 8
-    // );
-    let (a, b, c) = (15094, 15196, 3835); // polynomial fit to 4 above data points
-    let x = l1_radius as u64; // = 202300
-    a * x * x + b * x + c
+/// Further copies of a plot `slack` steps short of the limit, `m` tiles out
+/// for `m >= 1`, that are in reach, and if `parity` is set also an even
+/// number of steps short. Straight out there's one copy `m` tiles away, but
+/// out from a corner there are `m + 1`.
+fn tiles_beyond<const N: usize>(slack: usize, corner: bool, parity: bool) -> u64 {
+    let furthest = (slack / N) as u64;
+    // copy m has the right parity exactly when N * m has the same parity as
+    // slack, so every m if N is even, and every other m if N is odd
+    let (first, stride) = match (parity, N % 2, slack % 2) {
+        (false, _, _) => (1, 1),
+        (true, 0, 0) => (1, 1),
+        (true, 0, _) => return 0,
+        (true, _, 1) => (1, 2),
+        _ => (2, 2),
+    };
+    if furthest < first {
+        return 0;
+    }
+    let copies = (furthest - first) / stride + 1;
+    if corner {
+        // sum of (m + 1) over m = first, first + stride, ...
+        copies * (first + 1) + stride * copies * (copies - 1) / 2
+    } else {
+        copies
+    }
+}
+
+/// Plots reachable after exactly `steps` in the infinite garden. When the
+/// input has clear lanes through the start, the counts at `k * N + steps % N`
+/// steps are sampled for a few `k` of the same parity as the target, fitted
+/// to a quadratic with exact integer differences, and extrapolated once a
+/// fourth sample confirms the fit. Otherwise it counts tile by tile.
+fn day21_p2_v3<const N: usize>(data: &str, steps: usize) -> Result<u64, String> {
+    let (chart, start) = get_state::<N>(data);
+    let (tiles, offset) = (steps / N, steps % N);
+    // the first couple of tiles out aren't settled into the pattern yet
+    let first = 2 + tiles % 2;
+    if has_clear_lanes(&chart, start) && tiles >= first + 6 {
+        let samples = [0, 1, 2, 3].map(|j| (first + 2 * j) * N + offset);
+        let distances = wrapping_distances(&chart, start, samples[3]);
+        let [f0, f1, f2, f3] = samples.map(|s| count_reachable(&distances, s) as i128);
+        let (d1, d2) = (f1 - f0, f2 - 2 * f1 + f0);
+        if f0 + 3 * d1 + 3 * d2 == f3 {
+            let j = ((tiles - first) / 2) as i128;
+            return Ok((f0 + j * d1 + j * (j - 1) / 2 * d2) as u64);
+        }
+    }
+    Ok(GardenDistances::new(&chart, &[start])?.exactly(steps))
 }
 
 pub fn run_day21_p1() -> u64 {
@@ -306,7 +547,7 @@ pub fn run_day21_p1() -> u64 {
 pub fn run_day21_p2() -> u64 {
     let filename = "data/day_21.txt";
     let data = read_to_string(filename).unwrap();
-    day21_p2_v2(&data)
+    day21_p2_v3::<131>(&data, 26_501_365).unwrap_or_else(|err| panic!("{err}"))
 }
 
 fn main() {
@@ -366,6 +607,121 @@ mod test {
         // assert_eq!(day21_p2::<11, 5000>(EXAMPLE), 16733044);
     }
 
+    /// The example with the start's row and column, and the border, cleared
+    const LANES: &str = "\
+        ...........\n\
+        ......##.#.\n\
+        .###..#..#.\n\
+        ..#.#...#..\n\
+        ....#.#....\n\
+        .....S.....\n\
+        .##......#.\n\
+        .......##..\n\
+        .##.#.####.\n\
+        .##...#.##.\n\
+        ...........";
+
+    #[test]
+    fn test_day21_garden_distances() {
+        let (chart, start) = get_state::<11>(EXAMPLE);
+        assert!(!has_clear_lanes(&chart, start));
+        let garden = GardenDistances::new(&chart, &[start]).unwrap();
+        for (steps, expected) in [(6, 16), (10, 50), (50, 1594), (100, 6536), (500, 167004)] {
+            assert_eq!(garden.exactly(steps), expected);
+        }
+        assert_eq!(day21_p2_v3::<11>(EXAMPLE, 1000).unwrap(), 668697);
+        assert_eq!(day21_p2_v3::<11>(EXAMPLE, 5000).unwrap(), 16733044);
+
+        let distances = wrapping_distances(&chart, start, 60);
+        for steps in [0, 1, 7, 33, 60] {
+            let within = distances.iter().filter(|&&d| d <= steps).count() as u64;
+            assert_eq!(garden.within(steps), within);
+        }
+        assert_eq!(garden.within(0), 1);
+    }
+
+    /// Plots that can be ended on after exactly `steps` from any of the
+    /// starts, found by stepping every one of them out across the wrapping
+    /// garden together
+    fn brute_force(
+        chart: &[[Tile; 11]; 11],
+        starts: &[Coordinate<11>],
+        steps: usize,
+    ) -> HashSet<WrappingCoordinate<11>> {
+        let mut plots: HashSet<WrappingCoordinate<11>> =
+            starts.iter().map(|&start| start.into()).collect();
+        for _ in 0..steps {
+            plots = plots
+                .into_iter()
+                .flat_map(|pos| [pos.up(), pos.down(), pos.left(), pos.right()])
+                .filter(|pos| chart[pos.row][pos.col] != Tile::Rock)
+                .collect();
+        }
+        plots
+    }
+
+    #[test]
+    fn test_day21_garden_distances_starts() {
+        let (chart, start) = get_state::<11>(EXAMPLE);
+        let corner = Coordinate::from((0, 0));
+        let both = GardenDistances::new(&chart, &[start, corner]).unwrap();
+        assert_eq!(both.by_colour.len(), 1);
+        assert_eq!(both.within(0), 2);
+        assert_eq!(both.within(1), 2 + 2 + 4);
+        for steps in [0, 1, 5, 20, 37] {
+            let brute = brute_force(&chart, &[start, corner], steps);
+            assert_eq!(both.exactly(steps), brute.len() as u64);
+        }
+    }
+
+    #[test]
+    fn test_day21_garden_distances_mixed_starts() {
+        let open = ".".repeat(11) + "\n";
+        let open = open.repeat(5) + ".....S.....\n" + &open.repeat(5);
+        let (chart, start) = get_state::<11>(&open);
+        let starts = [start, Coordinate::from((5, 6))];
+        let garden = GardenDistances::new(&chart, &starts).unwrap();
+        assert_eq!(garden.by_colour.len(), 2);
+        assert_eq!(garden.exactly(4), 50);
+        let (example, start) = get_state::<11>(EXAMPLE);
+        let example_starts = [start, Coordinate::from((0, 1)), Coordinate::from((10, 10))];
+        let example_garden = GardenDistances::new(&example, &example_starts).unwrap();
+        for steps in [0, 1, 2, 4, 9, 16, 25, 40] {
+            let brute = brute_force(&chart, &starts, steps);
+            assert_eq!(garden.exactly(steps), brute.len() as u64);
+            let brute = brute_force(&example, &example_starts, steps);
+            assert_eq!(example_garden.exactly(steps), brute.len() as u64);
+            // anything reached in at most `steps` is reached in exactly
+            // `steps` or one fewer, though now some plots are both
+            let fewer = match steps {
+                0 => HashSet::new(),
+                _ => brute_force(&example, &example_starts, steps - 1),
+            };
+            let within = brute.union(&fewer).count() as u64;
+            assert_eq!(example_garden.within(steps), within, "{steps} steps");
+        }
+    }
+
+    #[test]
+    fn test_day21_p2_v3_quadratic() {
+        let (chart, start) = get_state::<11>(LANES);
+        assert!(has_clear_lanes(&chart, start));
+        let garden = GardenDistances::new(&chart, &[start]).unwrap();
+        for steps in [10, 27, 104, 115, 160, 203] {
+            let brute = count_reachable(&wrapping_distances(&chart, start, steps), steps);
+            assert_eq!(
+                day21_p2_v3::<11>(LANES, steps).unwrap(),
+                brute,
+                "{steps} steps"
+            );
+            assert_eq!(garden.exactly(steps), brute, "{steps} steps");
+        }
+        assert_eq!(
+            day21_p2_v3::<11>(LANES, 26_501_365).unwrap(),
+            garden.exactly(26_501_365)
+        );
+    }
+
     #[test]
     fn test_day21_p1() {
         assert_eq!(run_day21_p1(), 3733);
@@ -375,4 +731,44 @@ mod test {
     fn test_day21_p2() {
         assert_eq!(run_day21_p2(), 617_729_401_414_635);
     }
+
+    #[test]
+    fn test_day21_garden_distances_detours() {
+        // distances take 7 tiles out to settle into repeating
+        let detours = "\
+            ...........\n\
+            ....#......\n\
+            #..###....#\n\
+            #.#.....#..\n\
+            ...#.#.....\n\
+            .....S....#\n\
+            .#..####...\n\
+            ....#.....#\n\
+            .......#...\n\
+            ..........#\n\
+            ..#.......#";
+        let (chart, start) = get_state::<11>(detours);
+        let garden = GardenDistances::new(&chart, &[start]).unwrap();
+        for steps in [0, 3, 10, 31, 64, 99] {
+            let brute = brute_force(&chart, &[start], steps);
+            assert_eq!(garden.exactly(steps), brute.len() as u64, "{steps} steps");
+        }
+        // every row has a rock in it, so crossing a tile sideways takes more
+        // than 11 steps and the distances never repeat
+        let blocked = "\
+            ..#.#......\n\
+            ..##.......\n\
+            ##.##......\n\
+            .#...#.....\n\
+            .......##.#\n\
+            #...#S.....\n\
+            ##.........\n\
+            .#.........\n\
+            ....#......\n\
+            .....#.....\n\
+            #....##....";
+        let (chart, start) = get_state::<11>(blocked);
+        let err = GardenDistances::new(&chart, &[start]).unwrap_err();
+        assert!(err.contains("within 11 tiles"), "{err}");
+    }
 }
@@ -1,12 +1,15 @@
 //! Day 22: Sand Slabs
+use aoc_2023::dot;
 use core::ops::Range;
+use itertools::Itertools;
 use std::cmp::Ordering;
 use std::env;
+use std::fmt;
 use std::fs::read_to_string;
 use std::time::Instant;
 
 #[derive(Debug, Eq, PartialEq, Clone)]
-struct Brick {
+pub struct Brick {
     x: Range<u16>,
     y: Range<u16>,
     z: Range<u16>,
@@ -50,6 +53,22 @@ impl From<&str> for Brick {
     }
 }
 
+/// Back into the puzzle's inclusive `x,y,z~x,y,z` syntax
+impl fmt::Display for Brick {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{},{},{}~{},{},{}",
+            self.x.start,
+            self.y.start,
+            self.z.start,
+            self.x.end - 1,
+            self.y.end - 1,
+            self.z.end - 1
+        )
+    }
+}
+
 impl Brick {
     fn move_down(&mut self, steps: u16) {
         self.z.start -= steps;
@@ -61,120 +80,457 @@ impl Brick {
             && (self.y.end > other.y.start && other.y.end > self.y.start)
     }
 
+    #[cfg(any(test, feature = "debug-validation"))]
     fn supports(&self, other: &Self) -> bool {
         self.z.end == other.z.start && self.xy_overlap(other)
     }
+
+    fn overlaps(&self, other: &Self) -> bool {
+        self.xy_overlap(other) && self.z.end > other.z.start && other.z.end > self.z.start
+    }
+
+    /// Top of the highest brick in `below` that this one would land on
+    /// if it fell, or the lowest z (1) if nothing is in the way
+    fn landing(&self, below: &[Brick]) -> u16 {
+        below
+            .iter()
+            .filter(|b| b.z.end <= self.z.start && b.xy_overlap(self))
+            .map(|b| b.z.end)
+            .max()
+            .unwrap_or(1)
+    }
 }
 
-fn settle_bricks(bricks: &mut Vec<Brick>) {
-    bricks.sort_unstable();
-    // settle the bricks
-    let mut changed = true;
-    while changed {
-        changed = false;
-        let mut prev_layer: Vec<&Brick> = vec![];
-        let mut this_layer: Vec<&Brick> = vec![];
-        let mut z_level = 1;
-        for brick in bricks.iter_mut() {
-            if brick.z.start != z_level {
-                z_level = brick.z.start;
-                prev_layer = prev_layer
-                    .into_iter()
-                    .chain(this_layer)
-                    .filter(|x| x.z.contains(&(z_level - 1)))
-                    .collect();
-                this_layer = vec![];
+/// Who rests on whom once the bricks have settled, by index into the
+/// settled bricks. Those are sorted by height, so every brick comes after
+/// all of the bricks holding it up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SupportGraph {
+    /// bricks directly underneath each brick, empty if it's on the ground
+    supported_by: Vec<Vec<usize>>,
+    /// bricks resting directly on top of each brick
+    supports: Vec<Vec<usize>>,
+}
+
+impl SupportGraph {
+    fn new(supported_by: Vec<Vec<usize>>) -> Self {
+        let mut supports = vec![vec![]; supported_by.len()];
+        for (ii, below) in supported_by.iter().enumerate() {
+            for &jj in below {
+                supports[jj].push(ii);
             }
-            if z_level > 1 {
-                if prev_layer.is_empty()
-                    || !prev_layer
-                        .iter()
-                        .filter(|b| b.z.end == z_level)
-                        .any(|b| b.xy_overlap(brick))
-                {
-                    brick.move_down(1);
-                    changed = true;
-                    prev_layer.push(brick);
-                } else {
-                    this_layer.push(brick);
+        }
+        Self {
+            supported_by,
+            supports,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.supports.len()
+    }
+
+    /// Bricks that can be disintegrated without anything else falling,
+    /// because everything on top of them has another support
+    fn safe_to_remove(&self) -> Vec<usize> {
+        (0..self.len())
+            .filter(|&ii| {
+                self.supports[ii]
+                    .iter()
+                    .all(|&above| self.supported_by[above].len() > 1)
+            })
+            .collect()
+    }
+
+    /// Immediate dominator of each brick, with the ground as the root
+    /// (`None`). A brick falls exactly when one of its dominators is
+    /// removed, and its immediate dominator is the nearest common one of
+    /// all of its supports.
+    fn dominators(&self) -> Vec<Option<usize>> {
+        let mut idom: Vec<Option<usize>> = vec![None; self.len()];
+        let mut depth = vec![0; self.len()];
+        for ii in 0..self.len() {
+            let mut supports = self.supported_by[ii].iter().map(|&jj| Some(jj));
+            let Some(first) = supports.next() else {
+                continue;
+            };
+            let dom = supports.fold(first, |a, b| {
+                let (mut a, mut b) = (a, b);
+                while a != b {
+                    // step up from whichever is deeper; the ground is depth 0
+                    let depth_of = |x: Option<usize>| x.map_or(0, |x| depth[x]);
+                    if depth_of(a) >= depth_of(b) {
+                        a = a.and_then(|x| idom[x]);
+                    } else {
+                        b = b.and_then(|x| idom[x]);
+                    }
                 }
-            } else {
-                this_layer.push(brick);
+                a
+            });
+            idom[ii] = dom;
+            depth[ii] = dom.map_or(0, |d| depth[d]) + 1;
+        }
+        idom
+    }
+
+    /// How many other bricks fall if each brick is disintegrated, which is
+    /// the size of its subtree in the dominator tree
+    fn chain_reaction(&self) -> Vec<usize> {
+        let idom = self.dominators();
+        let mut falls = vec![0; self.len()];
+        // dominators always come earlier, so children are done first
+        for ii in (0..self.len()).rev() {
+            if let Some(dom) = idom[ii] {
+                falls[dom] += falls[ii] + 1;
             }
+        }
+        falls
+    }
 
-            assert!(prev_layer.iter().all(|b| b.z.contains(&(z_level - 1))));
-            assert!(this_layer.iter().all(|b| b.z.contains(&z_level)));
-            assert!(prev_layer.iter().all(|b| prev_layer
-                .iter()
-                .filter(|b2| b.xy_overlap(b2))
-                .count()
-                == 1)); // 1 overlap with itself
-            assert!(this_layer.iter().all(|b| this_layer
+    /// Topological layer of each brick: 0 on the ground, otherwise one above
+    /// its highest layered support
+    fn layers(&self) -> Vec<usize> {
+        let mut layers = vec![0; self.len()];
+        for ii in 0..self.len() {
+            layers[ii] = self.supported_by[ii]
                 .iter()
-                .filter(|b2| b.xy_overlap(b2))
-                .count()
-                == 1)); // 1 overlap with itself
+                .map(|&jj| layers[jj] + 1)
+                .max()
+                .unwrap_or(0);
         }
-        // the need to re-sort is subtle
-        bricks.sort_unstable();
+        layers
+    }
+
+    /// Graphviz DOT with an edge from each brick to the ones resting on it,
+    /// and each brick labelled with its index, extent and layer. Render with
+    /// `dot -Grankdir=BT` to have the tower stand upright.
+    fn to_dot(&self, bricks: &[Brick]) -> dot::Graph {
+        let mut graph = dot::Graph::directed("bricks");
+        graph.node("ground", &[("shape", "box")]);
+        for ((ii, brick), layer) in bricks.iter().enumerate().zip(self.layers()) {
+            let label = format!("{ii}: {brick} (layer {layer})");
+            graph.node(&ii.to_string(), &[("label", &label)]);
+        }
+        for (ii, supported_by) in self.supported_by.iter().enumerate() {
+            if supported_by.is_empty() {
+                graph.edge("ground", &ii.to_string(), &[]);
+            }
+            for jj in supported_by {
+                graph.edge(&jj.to_string(), &ii.to_string(), &[]);
+            }
+        }
+        graph
     }
 }
 
-fn day22_p1(data: &str) -> u64 {
-    let mut bricks: Vec<Brick> = data.lines().map(Brick::from).collect();
-    settle_bricks(&mut bricks);
+/// The tower seen from above: how high the stack is in each column
+/// and which brick is on top of it
+struct HeightMap {
+    width: usize,
+    height: Vec<u16>,
+    owner: Vec<Option<usize>>,
+}
 
-    bricks
-        .iter()
-        .enumerate()
-        .map(|(ii, brick)| {
-            bricks
-                .iter()
-                .skip(ii)
-                .filter(|b| brick.supports(b)) // of all bricks supported by this brick
-                .map(|supported| {
-                    // is this supported brick only supported by 1 brick (ie this brick)
-                    (bricks.iter().filter(|b| b.supports(supported)).count() == 1) as usize
-                })
-                .sum::<usize>() // return how many brick are uniquely supported by this brick
+impl HeightMap {
+    fn new(bricks: &[Brick]) -> Self {
+        let width = bricks.iter().map(|b| b.x.end).max().unwrap_or(0) as usize;
+        let depth = bricks.iter().map(|b| b.y.end).max().unwrap_or(0) as usize;
+        Self {
+            width,
+            // the ground is at z = 0, so the lowest a brick can sit is 1
+            height: vec![1; width * depth],
+            owner: vec![None; width * depth],
+        }
+    }
+
+    fn cells(&self, brick: &Brick) -> impl Iterator<Item = usize> + '_ {
+        let xs = brick.x.clone();
+        brick.y.clone().flat_map(move |y| {
+            xs.clone()
+                .map(move |x| y as usize * self.width + x as usize)
         })
-        .filter(|&x| x == 0) // how many brick are not uniquely supporting a brick?
-        .count() as u64
-}
+    }
 
-fn day22_p2(data: &str) -> u64 {
-    let mut bricks: Vec<Brick> = data.lines().map(Brick::from).collect();
-    settle_bricks(&mut bricks);
+    /// Drops the brick straight down onto the columns under it, marking it as
+    /// brick `id`, and returns the ids of the bricks it lands on
+    fn drop(&mut self, id: usize, brick: &mut Brick) -> Vec<usize> {
+        let top = self
+            .cells(brick)
+            .map(|cell| self.height[cell])
+            .max()
+            .unwrap();
+        brick.move_down(brick.z.start - top);
+        let mut supports: Vec<usize> = self
+            .cells(brick)
+            .filter(|&cell| self.height[cell] == top)
+            .filter_map(|cell| self.owner[cell])
+            .collect();
+        supports.sort_unstable();
+        supports.dedup();
+        let cells: Vec<usize> = self.cells(brick).collect();
+        for cell in cells {
+            self.height[cell] = brick.z.end;
+            self.owner[cell] = Some(id);
+        }
+        supports
+    }
+}
 
+/// Drops the bricks until they're all resting on the ground or each other,
+/// leaving them sorted, and returns what supports what. Going from the lowest
+/// up, each brick only has to look at the height map under its own footprint,
+/// so this is a single pass after sorting.
+fn settle_bricks(bricks: &mut [Brick]) -> SupportGraph {
+    bricks.sort_unstable();
+    let mut map = HeightMap::new(bricks);
     let supported_by: Vec<Vec<usize>> = bricks
-        .iter()
+        .iter_mut()
         .enumerate()
-        .map(|(ii, brick)| {
-            bricks
-                .iter()
-                .enumerate()
-                .take(ii)
-                .filter(|(_, b)| b.supports(brick))
-                .map(|(jj, _)| jj)
-                .collect()
+        .map(|(ii, brick)| map.drop(ii, brick))
+        .collect();
+
+    // bricks can land lower than ones dropped before them, so re-sort and
+    // renumber to keep the graph indexed by the settled order
+    let mut order: Vec<usize> = (0..bricks.len()).collect();
+    order.sort_unstable_by(|&a, &b| bricks[a].cmp(&bricks[b]));
+    let mut rank = vec![0; bricks.len()];
+    for (new, &old) in order.iter().enumerate() {
+        rank[old] = new;
+    }
+    let supported_by = order
+        .iter()
+        .map(|&old| {
+            let mut supports: Vec<usize> = supported_by[old].iter().map(|&jj| rank[jj]).collect();
+            supports.sort_unstable();
+            supports
         })
         .collect();
+    let settled: Vec<Brick> = order.iter().map(|&old| bricks[old].clone()).collect();
+    bricks.clone_from_slice(&settled);
+
+    let graph = SupportGraph::new(supported_by);
+    #[cfg(feature = "debug-validation")]
+    validate(bricks, &graph);
+    graph
+}
+
+/// Checks the settled tower against its definition, pairwise: no two bricks
+/// overlap, and everything rests on exactly the bricks the graph claims.
+/// Quadratic, so only built with `--features debug-validation`.
+#[cfg(feature = "debug-validation")]
+fn validate(bricks: &[Brick], graph: &SupportGraph) {
+    assert!(bricks.windows(2).all(|pair| pair[0] <= pair[1]));
+    for (ii, brick) in bricks.iter().enumerate() {
+        assert!(bricks
+            .iter()
+            .skip(ii + 1)
+            .all(|b| !brick.xy_overlap(b) || b.z.start >= brick.z.end));
+        let supported_by: Vec<usize> = (0..ii).filter(|&jj| bricks[jj].supports(brick)).collect();
+        assert_eq!(supported_by, graph.supported_by[ii], "supports of {brick}");
+        assert_eq!(
+            supported_by.is_empty(),
+            brick.z.start == 1,
+            "{brick} is floating"
+        );
+    }
+}
+
+/// A brick that fell during a what-if, identified by where it was before
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fall {
+    pub brick: Brick,
+    pub distance: u16,
+}
 
-    (0..bricks.len())
-        .map(|ii| {
-            // if brick ii falls, what else falls
-            let mut fallen = vec![ii];
-            for (jj, supports) in supported_by.iter().enumerate().skip(ii + 1) {
-                // if all of the supports of this brick have fallen
-                if !supports.is_empty() && supports.iter().all(|support| fallen.contains(support)) {
-                    // then it has fallen too
-                    fallen.push(jj);
+impl fmt::Display for Fall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} fell {}", self.brick, self.distance)
+    }
+}
+
+/// Settled bricks along with their support graph, for playing Jenga with
+#[derive(Debug, Clone)]
+pub struct Tower {
+    bricks: Vec<Brick>,
+    graph: SupportGraph,
+}
+
+impl Tower {
+    pub fn new(mut bricks: Vec<Brick>) -> Self {
+        let graph = settle_bricks(&mut bricks);
+        Self { bricks, graph }
+    }
+
+    /// Takes out the bricks at the given indices and lets the tower
+    /// settle again. Only bricks resting, directly or not, on one of the
+    /// removed ones can move, so only those get dropped again, looking
+    /// down the columns they cover for what they land on. Everything else
+    /// keeps its place and its supports.
+    pub fn remove(&self, removed: &[usize]) -> (Tower, Vec<Fall>) {
+        let count = self.bricks.len();
+        let mut gone = vec![false; count];
+        let mut loose = vec![false; count];
+        for &ii in removed {
+            gone[ii] = true;
+        }
+        // bricks are in height order, so everything above a loose brick is
+        // found before we get there
+        for ii in 0..count {
+            if gone[ii] || loose[ii] {
+                for &above in &self.graph.supports[ii] {
+                    loose[above] = !gone[above];
                 }
             }
-            // the first brick doesn't count
-            fallen.len() - 1
-        })
-        .sum::<usize>() as u64
+        }
+
+        // which bricks, by their old index, stand in each x-y column
+        let width = self.bricks.iter().map(|b| b.x.end).max().unwrap_or(0) as usize;
+        let depth = self.bricks.iter().map(|b| b.y.end).max().unwrap_or(0) as usize;
+        let cells = |brick: &Brick| {
+            let (xs, ys) = (brick.x.clone(), brick.y.clone());
+            xs.flat_map(move |x| ys.clone().map(move |y| y as usize * width + x as usize))
+        };
+        let mut columns: Vec<Vec<usize>> = vec![vec![]; width * depth];
+        for ii in (0..count).filter(|&ii| !gone[ii] && !loose[ii]) {
+            for cell in cells(&self.bricks[ii]) {
+                columns[cell].push(ii);
+            }
+        }
+
+        // nothing still standing rested on a removed brick, so only the
+        // loose bricks need new supports
+        let mut bricks = self.bricks.clone();
+        let mut supported_by = self.graph.supported_by.clone();
+        let mut falls = vec![];
+        for ii in (0..count).filter(|&ii| loose[ii]) {
+            let start = bricks[ii].z.start;
+            let under: Vec<usize> = cells(&bricks[ii])
+                .flat_map(|cell| columns[cell].iter().copied())
+                .filter(|&jj| bricks[jj].z.end <= start)
+                .collect();
+            let landing = under.iter().map(|&jj| bricks[jj].z.end).max().unwrap_or(1);
+            let mut supports: Vec<usize> = under
+                .into_iter()
+                .filter(|&jj| bricks[jj].z.end == landing)
+                .collect();
+            supports.sort_unstable();
+            supports.dedup();
+            supported_by[ii] = supports;
+            if start > landing {
+                falls.push(Fall {
+                    brick: bricks[ii].clone(),
+                    distance: start - landing,
+                });
+                bricks[ii].move_down(start - landing);
+            }
+            for cell in cells(&bricks[ii]) {
+                columns[cell].push(ii);
+            }
+        }
+
+        // the bricks that stayed put are still in order, so only the
+        // dropped ones need sorting before merging the two back together
+        let mut dropped: Vec<usize> = (0..count).filter(|&ii| loose[ii]).collect();
+        dropped.sort_unstable_by(|&a, &b| bricks[a].cmp(&bricks[b]));
+        let stayed = (0..count).filter(|&ii| !gone[ii] && !loose[ii]);
+        let order: Vec<usize> = stayed
+            .merge_by(dropped, |&a, &b| bricks[a] <= bricks[b])
+            .collect();
+        let mut rank = vec![usize::MAX; count];
+        for (new, &old) in order.iter().enumerate() {
+            rank[old] = new;
+        }
+        let supported_by = order
+            .iter()
+            .map(|&old| {
+                let mut supports: Vec<usize> =
+                    supported_by[old].iter().map(|&jj| rank[jj]).collect();
+                supports.sort_unstable();
+                supports
+            })
+            .collect();
+        let bricks: Vec<Brick> = order.iter().map(|&old| bricks[old].clone()).collect();
+        let graph = SupportGraph::new(supported_by);
+        #[cfg(feature = "debug-validation")]
+        validate(&bricks, &graph);
+        (Tower { bricks, graph }, falls)
+    }
+
+    /// Drops new bricks into the tower from wherever they're given. Nothing
+    /// already in the tower moves, but the new bricks can't start inside it
+    /// or in the ground.
+    pub fn add(&self, added: &[Brick]) -> Result<(Tower, Vec<Fall>), String> {
+        let mut added = added.to_vec();
+        added.sort_unstable();
+        let mut bricks = self.bricks.clone();
+        let mut falls = vec![];
+        for mut brick in added {
+            if brick.z.start == 0 {
+                return Err(format!("{brick} is in the ground"));
+            }
+            if let Some(other) = bricks.iter().find(|b| b.overlaps(&brick)) {
+                return Err(format!("{brick} is inside {other}"));
+            }
+            let distance = brick.z.start - brick.landing(&bricks);
+            if distance > 0 {
+                falls.push(Fall {
+                    brick: brick.clone(),
+                    distance,
+                });
+                brick.move_down(distance);
+            }
+            bricks.push(brick);
+        }
+        Ok((Tower::new(bricks), falls))
+    }
+}
+
+/// `day22 remove 3 5 ...` or `day22 add 1,1,300~1,2,300 ...` to see
+/// what happens to the tower
+pub fn run_day22_what_if(mode: &str, args: &[String]) -> Result<(), String> {
+    let filename = "data/day_22.txt";
+    let data = read_to_string(filename).unwrap();
+    let tower = Tower::new(data.lines().map(Brick::from).collect());
+    let (_, falls) = if mode == "remove" {
+        let mut removed = vec![];
+        for arg in args {
+            match arg.parse::<usize>() {
+                Ok(ii) if ii < tower.bricks.len() => removed.push(ii),
+                _ => {
+                    return Err(format!(
+                        "{arg:?} isn't a brick, expected an index below {}",
+                        tower.bricks.len()
+                    ))
+                }
+            }
+        }
+        for &ii in &removed {
+            println!("removing {ii}: {}", tower.bricks[ii]);
+        }
+        tower.remove(&removed)
+    } else {
+        let added: Vec<Brick> = args.iter().map(|arg| Brick::from(arg.as_str())).collect();
+        tower
+            .add(&added)
+            .map_err(|err| format!("Can't add bricks: {err}"))?
+    };
+    for fall in &falls {
+        println!("{fall}");
+    }
+    println!("{} bricks fell", falls.len());
+    Ok(())
+}
+
+fn day22_p1(data: &str) -> u64 {
+    let mut bricks: Vec<Brick> = data.lines().map(Brick::from).collect();
+    let graph = settle_bricks(&mut bricks);
+    graph.safe_to_remove().len() as u64
+}
+
+fn day22_p2(data: &str) -> u64 {
+    let mut bricks: Vec<Brick> = data.lines().map(Brick::from).collect();
+    let graph = settle_bricks(&mut bricks);
+    graph.chain_reaction().into_iter().sum::<usize>() as u64
 }
 
 pub fn run_day22_p1() -> u64 {
@@ -190,6 +546,20 @@ pub fn run_day22_p2() -> u64 {
 }
 
 fn main() {
+    if env::args().nth(1).as_deref() == Some("dot") {
+        let data = read_to_string("data/day_22.txt").unwrap();
+        let mut bricks: Vec<Brick> = data.lines().map(Brick::from).collect();
+        let graph = settle_bricks(&mut bricks);
+        print!("{}", graph.to_dot(&bricks));
+        return;
+    }
+    if let Some(mode @ ("remove" | "add")) = env::args().nth(1).as_deref() {
+        let args: Vec<String> = env::args().skip(2).collect();
+        if let Err(err) = run_day22_what_if(mode, &args) {
+            eprintln!("{err}");
+        }
+        return;
+    }
     let part1 = if let Some(arg1) = env::args().nth(1) {
         arg1.parse().unwrap_or(1) == 1
     } else {
@@ -221,6 +591,106 @@ mod test {
         0,1,6~2,1,6\n\
         1,1,8~1,1,9";
 
+    /// The original settling, a unit step at a time until nothing moves,
+    /// kept to check the height map version against
+    fn settle_bricks_stepwise(bricks: &mut [Brick]) -> SupportGraph {
+        bricks.sort_unstable();
+        // settle the bricks
+        let mut changed = true;
+        while changed {
+            changed = false;
+            let mut prev_layer: Vec<&Brick> = vec![];
+            let mut this_layer: Vec<&Brick> = vec![];
+            let mut z_level = 1;
+            for brick in bricks.iter_mut() {
+                if brick.z.start != z_level {
+                    z_level = brick.z.start;
+                    prev_layer = prev_layer
+                        .into_iter()
+                        .chain(this_layer)
+                        .filter(|x| x.z.contains(&(z_level - 1)))
+                        .collect();
+                    this_layer = vec![];
+                }
+                if z_level > 1 {
+                    if prev_layer.is_empty()
+                        || !prev_layer
+                            .iter()
+                            .filter(|b| b.z.end == z_level)
+                            .any(|b| b.xy_overlap(brick))
+                    {
+                        brick.move_down(1);
+                        changed = true;
+                        prev_layer.push(brick);
+                    } else {
+                        this_layer.push(brick);
+                    }
+                } else {
+                    this_layer.push(brick);
+                }
+
+                assert!(prev_layer.iter().all(|b| b.z.contains(&(z_level - 1))));
+                assert!(this_layer.iter().all(|b| b.z.contains(&z_level)));
+                assert!(prev_layer.iter().all(|b| prev_layer
+                    .iter()
+                    .filter(|b2| b.xy_overlap(b2))
+                    .count()
+                    == 1)); // 1 overlap with itself
+                assert!(this_layer.iter().all(|b| this_layer
+                    .iter()
+                    .filter(|b2| b.xy_overlap(b2))
+                    .count()
+                    == 1)); // 1 overlap with itself
+            }
+            // the need to re-sort is subtle
+            bricks.sort_unstable();
+        }
+        let supported_by = (0..bricks.len())
+            .map(|ii| {
+                (0..ii)
+                    .filter(|&jj| bricks[jj].supports(&bricks[ii]))
+                    .collect()
+            })
+            .collect();
+        SupportGraph::new(supported_by)
+    }
+
+    /// Bricks up to 4 long in one direction, stacked at distinct heights so
+    /// none of them overlap before settling
+    fn random_bricks(seed: u64, count: usize) -> Vec<Brick> {
+        let mut state = seed;
+        let mut next = |modulus: u64| {
+            state = state
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            ((state >> 33) % modulus) as u16
+        };
+        (0..count as u16)
+            .map(|ii| {
+                let (x, y, z) = (next(6), next(6), 1 + 4 * ii);
+                let len = next(4);
+                let end = match next(3) {
+                    0 => (x + len, y, z),
+                    1 => (x, y + len, z),
+                    _ => (x, y, z + len),
+                };
+                Brick::from(format!("{x},{y},{z}~{},{},{}", end.0, end.1, end.2).as_str())
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_day22_settle_matches_stepwise() {
+        for seed in 0..50 {
+            let mut bricks = random_bricks(seed, 60);
+            let mut expected = bricks.clone();
+            let graph = settle_bricks(&mut bricks);
+            let expected_graph = settle_bricks_stepwise(&mut expected);
+            assert_eq!(bricks, expected, "seed {seed}");
+            assert_eq!(graph, expected_graph, "seed {seed}");
+        }
+    }
+
     #[test]
     fn test_day22_p1_example() {
         assert_eq!(day22_p1(EXAMPLE), 5);
@@ -231,6 +701,99 @@ mod test {
         assert_eq!(day22_p2(EXAMPLE), 7);
     }
 
+    #[test]
+    fn test_day22_support_graph() {
+        let mut bricks: Vec<Brick> = EXAMPLE.lines().map(Brick::from).collect();
+        let graph = settle_bricks(&mut bricks);
+        // A holds up B and C, which both hold up D and E, which both hold
+        // up F, which holds up G
+        assert_eq!(bricks[1].to_string(), "0,0,2~2,0,2");
+        assert_eq!(graph.supported_by[3], [1, 2]);
+        assert_eq!(graph.supports[0], [1, 2]);
+        assert_eq!(graph.safe_to_remove(), [1, 2, 3, 4, 6]);
+        assert_eq!(
+            graph.dominators(),
+            [None, Some(0), Some(0), Some(0), Some(0), Some(0), Some(5)]
+        );
+        assert_eq!(graph.chain_reaction(), [6, 0, 0, 0, 0, 1, 0]);
+        assert_eq!(graph.layers(), [0, 1, 1, 2, 2, 3, 4]);
+        let dot = graph.to_dot(&bricks).to_string();
+        assert!(dot.contains("\"0\" [label=\"0: 1,0,1~1,2,1 (layer 0)\"];"));
+        assert!(dot.contains("\"ground\" -> \"0\";"));
+        assert_eq!(dot.matches(" -> ").count(), 10);
+    }
+
+    #[test]
+    fn test_day22_tower_remove() {
+        let tower = Tower::new(EXAMPLE.lines().map(Brick::from).collect());
+        // taking out F drops G all the way down onto A
+        let (after, falls) = tower.remove(&[5]);
+        assert_eq!(
+            falls,
+            [Fall {
+                brick: Brick::from("1,1,5~1,1,6"),
+                distance: 3
+            }]
+        );
+        assert_eq!(after.bricks.len(), 6);
+        assert_eq!(after.bricks[3], Brick::from("1,1,2~1,1,3"));
+        assert_eq!(after.graph.supported_by[3], [0]);
+
+        // without A, everything else comes down one
+        let (after, falls) = tower.remove(&[0]);
+        assert_eq!(falls.len(), 6);
+        assert!(falls.iter().all(|fall| fall.distance == 1));
+        assert_eq!(after.bricks[0], Brick::from("0,0,1~2,0,1"));
+
+        // safe to remove means nothing falls
+        for ii in tower.graph.safe_to_remove() {
+            assert!(tower.remove(&[ii]).1.is_empty());
+        }
+        // removing both of D and E has the same effect as removing one of
+        // the dominators of F
+        let (_, falls) = tower.remove(&[3, 4]);
+        assert_eq!(falls.len(), 2);
+        assert_eq!(falls[0].to_string(), "0,1,4~2,1,4 fell 2");
+    }
+
+    #[test]
+    fn test_day22_tower_matches_resettle() {
+        for seed in 0..40 {
+            let bricks = random_bricks(seed, 40);
+            let tower = Tower::new(bricks);
+            let removed = [
+                seed as usize % 7,
+                10 + seed as usize % 13,
+                25 + seed as usize % 11,
+            ];
+            let (after, _) = tower.remove(&removed);
+            let kept: Vec<Brick> = (0..tower.bricks.len())
+                .filter(|ii| !removed.contains(ii))
+                .map(|ii| tower.bricks[ii].clone())
+                .collect();
+            let expected = Tower::new(kept);
+            assert_eq!(after.bricks, expected.bricks, "seed {seed}");
+            assert_eq!(after.graph, expected.graph, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn test_day22_tower_add() {
+        let tower = Tower::new(EXAMPLE.lines().map(Brick::from).collect());
+        let (after, falls) = tower
+            .add(&[Brick::from("1,1,20~1,1,20"), Brick::from("0,0,9~0,0,9")])
+            .unwrap();
+        assert_eq!(falls[0].to_string(), "0,0,9~0,0,9 fell 5");
+        assert_eq!(falls[1].to_string(), "1,1,20~1,1,20 fell 13");
+        assert_eq!(after.bricks.len(), 9);
+        assert_eq!(after.bricks[8], Brick::from("1,1,7~1,1,7"));
+        assert!(tower.add(&[Brick::from("1,1,6~1,1,6")]).is_err());
+        assert!(tower.add(&[Brick::from("2,2,0~2,2,0")]).is_err());
+        // a brick that's already resting on the tower doesn't fall at all
+        let (_, falls) = tower.add(&[Brick::from("2,2,1~2,2,1")]).unwrap();
+        assert!(falls.is_empty());
+    }
+
     #[test]
     fn test_day22_p1() {
         assert_eq!(run_day22_p1(), 485);
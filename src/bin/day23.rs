@@ -1,8 +1,9 @@
 //! Day 23: A Long Walk
-use rayon::prelude::*;
-use std::collections::HashSet;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::env;
 use std::fs::read_to_string;
+use std::iter;
 use std::time::Instant;
 
 // Coordinates for convenience. It encapsulates boundary conditions
@@ -77,7 +78,22 @@ enum Tile {
     LeftSlope,
 }
 
-fn get_state<const N: usize>(data: &str) -> ([[Tile; N]; N], Coordinate<N>) {
+impl From<Tile> for char {
+    fn from(tile: Tile) -> Self {
+        match tile {
+            Tile::Path => '.',
+            Tile::Forest => '#',
+            Tile::UpSlope => '^',
+            Tile::DownSlope => 'v',
+            Tile::RightSlope => '>',
+            Tile::LeftSlope => '<',
+        }
+    }
+}
+
+type Chart<const N: usize> = [[Tile; N]; N];
+
+fn get_state<const N: usize>(data: &str) -> (Chart<N>, Coordinate<N>, Coordinate<N>) {
     let mut chart = [[Tile::Forest; N]; N];
     for (chart_line, data_line) in chart
         .iter_mut()
@@ -95,115 +111,321 @@ fn get_state<const N: usize>(data: &str) -> ([[Tile; N]; N], Coordinate<N>) {
             };
         }
     }
-    let start = Coordinate {
-        row: 0,
-        col: chart[0]
+    let find_path = |row: usize| Coordinate {
+        row,
+        col: chart[row]
             .iter()
             .position(|&tile| tile == Tile::Path)
             .unwrap(),
     };
-    (chart, start)
+    (chart, find_path(0), find_path(N - 1))
 }
 
-fn find_longest_path<const N: usize>(
-    chart: &[[Tile; N]; N],
+/// What a slope tile means for walking onto it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SlopeMode {
+    /// only going downhill, i.e. in the direction it points (part 1)
+    Strict,
+    /// same as any other path (part 2)
+    Ignored,
+    /// any way, but going up or across a slope costs this many extra steps
+    Uphill(u32),
+}
+
+/// Whether to look for the longest or shortest route without revisiting tiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Goal {
+    Longest,
+    Shortest,
+}
+
+/// Tiles next to `pos` that can be stepped onto, along with what it costs
+/// to step there under `slopes`
+fn steps<const N: usize>(
+    chart: &Chart<N>,
     pos: Coordinate<N>,
-    mut used: HashSet<Coordinate<N>>,
-) -> Option<u32> {
-    let mut dist: u32 = 0;
-    let mut next_pos: Vec<Coordinate<N>> = vec![pos];
-    let mut pos = pos;
-    let mut last_pos;
-    while next_pos.len() == 1 {
-        last_pos = pos;
-        pos = next_pos.pop().unwrap();
-        dist += 1;
-        if pos.row == N - 1 {
-            return Some(dist);
+    slopes: SlopeMode,
+) -> impl Iterator<Item = (Coordinate<N>, u32)> + '_ {
+    [
+        (pos.up(), Tile::UpSlope),
+        (pos.down(), Tile::DownSlope),
+        (pos.right(), Tile::RightSlope),
+        (pos.left(), Tile::LeftSlope),
+    ]
+    .into_iter()
+    .filter_map(move |(nx_pos, slope)| {
+        let nx_pos = nx_pos?;
+        let tile = chart[nx_pos.row][nx_pos.col];
+        let cost = match (tile, slopes) {
+            (Tile::Forest, _) => None,
+            (Tile::Path, _) | (_, SlopeMode::Ignored) => Some(1),
+            _ if tile == slope => Some(1),
+            (_, SlopeMode::Strict) => None,
+            (_, SlopeMode::Uphill(extra)) => Some(1 + extra),
+        };
+        Some((nx_pos, cost?))
+    })
+}
+
+/// A corridor from one junction to another, costing `length` steps
+#[derive(Debug, Clone, PartialEq)]
+struct Corridor<const N: usize> {
+    to: usize,
+    length: u32,
+    /// every tile along the way, ending with the junction reached
+    tiles: Vec<Coordinate<N>>,
+}
+
+/// The maze with each corridor contracted down to a single weighted edge.
+/// The nodes are the start, the goal and every tile where paths meet.
+#[derive(Debug, Clone)]
+struct JunctionGraph<const N: usize> {
+    junctions: Vec<Coordinate<N>>,
+    corridors: Vec<Vec<Corridor<N>>>,
+    start: usize,
+    end: usize,
+}
+
+/// A route found through the maze, as the junctions it passes through and
+/// as every tile stepped on, both in order and including the start. The
+/// length includes any extra cost for climbing slopes.
+#[derive(Debug, Clone, PartialEq)]
+struct Hike<const N: usize> {
+    length: u32,
+    junctions: Vec<Coordinate<N>>,
+    route: Vec<Coordinate<N>>,
+}
+
+impl<const N: usize> Hike<N> {
+    /// The map with the route drawn on in `O`s, from the `S` at the start
+    fn render(&self, chart: &Chart<N>) -> String {
+        let mut map: Vec<Vec<char>> = chart
+            .iter()
+            .map(|row| row.iter().map(|&tile| char::from(tile)).collect())
+            .collect();
+        for pos in &self.route {
+            map[pos.row][pos.col] = 'O';
         }
-        next_pos = [
-            (pos.up().unwrap(), Tile::UpSlope),
-            (pos.down().unwrap(), Tile::DownSlope),
-            (pos.right().unwrap(), Tile::RightSlope),
-            (pos.left().unwrap(), Tile::LeftSlope),
-        ]
-        .into_iter()
-        .filter(|&(nx_pos, ttype)| {
-            (chart[nx_pos.row][nx_pos.col] == Tile::Path || chart[nx_pos.row][nx_pos.col] == ttype)
-                && (nx_pos != last_pos)
-                && (!used.contains(&nx_pos))
-        })
-        .map(|(nx_pos, _)| nx_pos)
-        .collect();
+        map[self.route[0].row][self.route[0].col] = 'S';
+        map.into_iter()
+            .map(|row| row.into_iter().collect::<String>() + "\n")
+            .collect()
     }
-    if next_pos.is_empty() {
-        return None;
+}
+
+impl<const N: usize> JunctionGraph<N> {
+    fn new(chart: &Chart<N>, start: Coordinate<N>, end: Coordinate<N>, slopes: SlopeMode) -> Self {
+        let mut junctions = vec![start, end];
+        for row in 0..N {
+            for col in 0..N {
+                let pos = Coordinate { row, col };
+                if chart[row][col] != Tile::Forest
+                    && !junctions.contains(&pos)
+                    && steps(chart, pos, SlopeMode::Ignored).count() > 2
+                {
+                    junctions.push(pos);
+                }
+            }
+        }
+        let corridors = junctions
+            .iter()
+            .map(|&junction| {
+                steps(chart, junction, slopes)
+                    .filter_map(|first| Self::walk(chart, &junctions, junction, first, slopes))
+                    .collect()
+            })
+            .collect();
+        Self {
+            junctions,
+            corridors,
+            start: 0,
+            end: 1,
+        }
+    }
+
+    /// Follows the corridor leaving `junction` via `first` to the next
+    /// junction, if it doesn't dead end
+    fn walk(
+        chart: &Chart<N>,
+        junctions: &[Coordinate<N>],
+        junction: Coordinate<N>,
+        (first, cost): (Coordinate<N>, u32),
+        slopes: SlopeMode,
+    ) -> Option<Corridor<N>> {
+        let (mut last_pos, mut pos, mut length) = (junction, first, cost);
+        let mut tiles = vec![first];
+        loop {
+            if let Some(to) = junctions.iter().position(|&j| j == pos) {
+                return Some(Corridor { to, length, tiles });
+            }
+            // a corridor tile only has one way on, if any
+            let (nx_pos, cost) = steps(chart, pos, slopes).find(|&(nx, _)| nx != last_pos)?;
+            (last_pos, pos, length) = (pos, nx_pos, length + cost);
+            tiles.push(pos);
+        }
+    }
+
+    fn hike(&self, length: u32, path: &[&Corridor<N>]) -> Hike<N> {
+        let start = self.junctions[self.start];
+        Hike {
+            length,
+            junctions: iter::once(start)
+                .chain(path.iter().map(|corridor| self.junctions[corridor.to]))
+                .collect(),
+            route: iter::once(start)
+                .chain(
+                    path.iter()
+                        .flat_map(|corridor| corridor.tiles.iter().copied()),
+                )
+                .collect(),
+        }
+    }
+
+    /// Dijkstra over the junctions. With every step costing something the
+    /// shortest route never comes back on itself, so it's simple for free.
+    fn shortest_hike(&self) -> Option<Hike<N>> {
+        let mut dist = vec![u32::MAX; self.junctions.len()];
+        let mut via: Vec<Option<(usize, &Corridor<N>)>> = vec![None; self.junctions.len()];
+        let mut queue = BinaryHeap::from([Reverse((0, self.start))]);
+        dist[self.start] = 0;
+        while let Some(Reverse((length, node))) = queue.pop() {
+            if node == self.end {
+                let mut path = vec![];
+                let mut node = node;
+                while let Some((prev, corridor)) = via[node] {
+                    path.push(corridor);
+                    node = prev;
+                }
+                path.reverse();
+                return Some(self.hike(length, &path));
+            }
+            if length > dist[node] {
+                continue;
+            }
+            for corridor in &self.corridors[node] {
+                let length = length + corridor.length;
+                if length < dist[corridor.to] {
+                    dist[corridor.to] = length;
+                    via[corridor.to] = Some((node, corridor));
+                    queue.push(Reverse((length, corridor.to)));
+                }
+            }
+        }
+        None
+    }
+
+    /// Longest simple path from start to end by DFS over a bitset of the
+    /// visited junctions. A junction can only be entered once, so the length
+    /// so far plus the longest way into each unvisited junction bounds what
+    /// a branch can reach, and branches that can't beat the best are cut.
+    fn longest_hike(&self) -> Option<Hike<N>> {
+        let mut longest_in = vec![0; self.junctions.len()];
+        for corridor in self.corridors.iter().flatten() {
+            longest_in[corridor.to] = longest_in[corridor.to].max(corridor.length);
+        }
+        // if only one junction leads to the end, going anywhere else from
+        // it means never getting there
+        let into_end: Vec<usize> = (0..self.junctions.len())
+            .filter(|&ii| self.corridors[ii].iter().any(|c| c.to == self.end))
+            .collect();
+        let last_junction = (into_end.len() == 1).then(|| into_end[0]);
+
+        let mut search = Search {
+            graph: self,
+            longest_in,
+            last_junction,
+            visited: vec![0; self.junctions.len().div_ceil(64)],
+            path: vec![],
+            best: None,
+        };
+        let bound = search.longest_in.iter().sum::<u32>() - search.longest_in[self.start];
+        search.visit(self.start, 0, bound);
+        let (length, path) = search.best?;
+        Some(self.hike(length, &path))
     }
-    used.insert(pos);
-    dist += next_pos
-        .into_iter()
-        .map(|nx_pos| find_longest_path(chart, nx_pos, used.clone()))
-        .flatten()
-        .max()?;
-    Some(dist)
 }
 
-fn day23_p1<const N: usize>(data: &str) -> u32 {
-    let (chart, start) = get_state::<N>(data);
-    let pos = start.down().unwrap();
-    let mut used = HashSet::new();
-    used.insert(start);
-    find_longest_path(&chart, pos, used).unwrap()
+struct Search<'a, const N: usize> {
+    graph: &'a JunctionGraph<N>,
+    longest_in: Vec<u32>,
+    last_junction: Option<usize>,
+    /// one bit per junction on the current path
+    visited: Vec<u64>,
+    path: Vec<&'a Corridor<N>>,
+    best: Option<(u32, Vec<&'a Corridor<N>>)>,
 }
 
-fn find_longest_path_p2<const N: usize>(
-    chart: &[[Tile; N]; N],
-    pos: Coordinate<N>,
-    mut used: HashSet<Coordinate<N>>,
-) -> Option<u32> {
-    let mut dist: u32 = 0;
-    let mut next_pos: Vec<Coordinate<N>> = vec![pos];
-    let mut pos = pos;
-    let mut last_pos;
-    while next_pos.len() == 1 {
-        last_pos = pos;
-        pos = next_pos.pop().unwrap();
-        dist += 1;
-        if pos.row == N - 1 {
-            return Some(dist);
+impl<'a, const N: usize> Search<'a, N> {
+    fn is_visited(&self, node: usize) -> bool {
+        self.visited[node / 64] & (1 << (node % 64)) != 0
+    }
+
+    fn toggle(&mut self, node: usize) {
+        self.visited[node / 64] ^= 1 << (node % 64);
+    }
+
+    fn visit(&mut self, node: usize, length: u32, bound: u32) {
+        self.toggle(node);
+        self.search_from(node, length, bound);
+        self.toggle(node);
+    }
+
+    fn search_from(&mut self, node: usize, length: u32, bound: u32) {
+        if node == self.graph.end {
+            if !matches!(self.best, Some((best, _)) if best >= length) {
+                self.best = Some((length, self.path.clone()));
+            }
+            return;
+        }
+        if matches!(self.best, Some((best, _)) if length + bound <= best) {
+            return;
+        }
+        let graph: &'a JunctionGraph<N> = self.graph;
+        for corridor in &graph.corridors[node] {
+            if self.is_visited(corridor.to)
+                || (self.last_junction == Some(node) && corridor.to != graph.end)
+            {
+                continue;
+            }
+            self.path.push(corridor);
+            self.visit(
+                corridor.to,
+                length + corridor.length,
+                bound - self.longest_in[corridor.to],
+            );
+            self.path.pop();
         }
-        next_pos = [
-            pos.up().unwrap(),
-            pos.down().unwrap(),
-            pos.right().unwrap(),
-            pos.left().unwrap(),
-        ]
-        .into_iter()
-        .filter(|&nx_pos| {
-            (chart[nx_pos.row][nx_pos.col] != Tile::Forest)
-                && (nx_pos != last_pos)
-                && (!used.contains(&nx_pos))
-        })
-        .collect();
     }
-    if next_pos.is_empty() {
-        return None;
+}
+
+/// The longest or shortest simple route from `start` to `end`, if there's
+/// any way there at all
+fn hike<const N: usize>(
+    chart: &Chart<N>,
+    start: Coordinate<N>,
+    end: Coordinate<N>,
+    slopes: SlopeMode,
+    goal: Goal,
+) -> Option<Hike<N>> {
+    let graph = JunctionGraph::new(chart, start, end, slopes);
+    match goal {
+        Goal::Longest => graph.longest_hike(),
+        Goal::Shortest => graph.shortest_hike(),
     }
-    used.insert(pos);
-    dist += next_pos
-        .into_par_iter()
-        .map(|nx_pos| find_longest_path_p2(chart, nx_pos, used.clone()))
-        .flatten()
-        .max()?;
-    Some(dist)
+}
+
+fn day23_p1<const N: usize>(data: &str) -> u32 {
+    let (chart, start, end) = get_state::<N>(data);
+    hike(&chart, start, end, SlopeMode::Strict, Goal::Longest)
+        .unwrap()
+        .length
 }
 
 fn day23_p2<const N: usize>(data: &str) -> u32 {
-    let (chart, start) = get_state::<N>(data);
-    let pos = start.down().unwrap();
-    let mut used = HashSet::new();
-    used.insert(start);
-    find_longest_path_p2(&chart, pos, used).unwrap()
+    let (chart, start, end) = get_state::<N>(data);
+    hike(&chart, start, end, SlopeMode::Ignored, Goal::Longest)
+        .unwrap()
+        .length
 }
 
 pub fn run_day23_p1() -> u32 {
@@ -213,13 +435,33 @@ pub fn run_day23_p1() -> u32 {
 }
 
 pub fn run_day23_p2() -> u32 {
-    // super slow
     let filename = "data/day_23.txt";
     let data = read_to_string(filename).unwrap();
     day23_p2::<141>(&data)
 }
 
 fn main() {
+    if env::args().nth(1).as_deref() == Some("route") {
+        // `day23 route [1|2|uphill:COST] [shortest]` draws the longest (or
+        // shortest) hike for that part, or with slopes climbable at a cost
+        let slopes = match env::args().nth(2).as_deref() {
+            Some("2") => SlopeMode::Ignored,
+            Some(arg) if arg.starts_with("uphill:") => {
+                SlopeMode::Uphill(arg["uphill:".len()..].parse().unwrap())
+            }
+            _ => SlopeMode::Strict,
+        };
+        let goal = match env::args().nth(3).as_deref() {
+            Some("shortest") => Goal::Shortest,
+            _ => Goal::Longest,
+        };
+        let data = read_to_string("data/day_23.txt").unwrap();
+        let (chart, start, end) = get_state::<141>(&data);
+        let hike = hike(&chart, start, end, slopes, goal).unwrap();
+        print!("{}", hike.render(&chart));
+        println!("{} steps", hike.length);
+        return;
+    }
     let part1 = if let Some(arg1) = env::args().nth(1) {
         arg1.parse().unwrap_or(1) == 1
     } else {
@@ -241,6 +483,7 @@ fn main() {
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::collections::HashSet;
 
     const EXAMPLE: &str = "\
         #.#####################\n\
@@ -277,15 +520,167 @@ mod test {
         assert_eq!(day23_p2::<23>(EXAMPLE), 154);
     }
 
+    #[test]
+    fn test_day23_junction_graph() {
+        let (chart, start, end) = get_state::<23>(EXAMPLE);
+        let graph = JunctionGraph::new(&chart, start, end, SlopeMode::Ignored);
+        assert_eq!(graph.junctions.len(), 9);
+        assert_eq!(graph.corridors[graph.start][0].to, 3);
+        assert_eq!(graph.corridors[graph.start][0].length, 15);
+        // corridors go both ways without slopes, but only downhill with them
+        let count = |graph: &JunctionGraph<23>| graph.corridors.iter().flatten().count();
+        assert_eq!(count(&graph), 24);
+        let graph = JunctionGraph::new(&chart, start, end, SlopeMode::Strict);
+        assert_eq!(count(&graph), 12);
+
+        let hike = graph.longest_hike().unwrap();
+        assert_eq!(hike.length, 94);
+        assert_eq!(hike.junctions[0], start);
+        assert_eq!(hike.junctions.last(), Some(&end));
+        assert_eq!(hike.junctions[1], Coordinate::from((5, 3)));
+    }
+
+    /// Checks the route is a simple path of single steps from start to end,
+    /// only entering slopes downhill if `slopes`
+    fn check_route(chart: &Chart<23>, hike: &Hike<23>, slopes: SlopeMode) {
+        let (_, start, end) = get_state::<23>(EXAMPLE);
+        assert_eq!(hike.route.first(), Some(&start));
+        assert_eq!(hike.route.last(), Some(&end));
+        assert_eq!(hike.route.len() as u32, hike.length + 1);
+        let unique: HashSet<_> = hike.route.iter().collect();
+        assert_eq!(unique.len(), hike.route.len());
+        for pair in hike.route.windows(2) {
+            assert!(steps(chart, pair[0], slopes).any(|(nx, _)| nx == pair[1]));
+        }
+        assert!(hike.junctions.iter().all(|j| hike.route.contains(j)));
+    }
+
+    #[test]
+    fn test_day23_route() {
+        let (chart, start, end) = get_state::<23>(EXAMPLE);
+        let hike = hike(&chart, start, end, SlopeMode::Strict, Goal::Longest).unwrap();
+        check_route(&chart, &hike, SlopeMode::Strict);
+        let map = hike.render(&chart);
+        assert_eq!(map.matches('O').count(), 94);
+        assert!(map.starts_with("#S#####################\n#OOOOOOO#########...###\n"));
+
+        let hike = super::hike(&chart, start, end, SlopeMode::Ignored, Goal::Longest).unwrap();
+        check_route(&chart, &hike, SlopeMode::Ignored);
+        assert_eq!(hike.length, 154);
+        // the longer part 2 route has to go up at least one slope
+        assert!(hike
+            .route
+            .windows(2)
+            .any(|pair| !steps(&chart, pair[0], SlopeMode::Strict).any(|(nx, _)| nx == pair[1])));
+    }
+
+    #[test]
+    fn test_day23_hike_options() {
+        use Goal::*;
+        use SlopeMode::*;
+        let (chart, start, end) = get_state::<23>(EXAMPLE);
+        // somewhere in the middle of a corridor
+        let middle = Coordinate::from((1, 5));
+        let cases = [
+            (start, end, Strict, Shortest, Some(74)),
+            (start, end, Ignored, Shortest, Some(74)),
+            (start, end, Uphill(0), Longest, Some(154)),
+            (start, end, Uphill(10), Longest, Some(194)),
+            // going back up to the start is only possible ignoring slopes
+            (end, start, Strict, Longest, None),
+            (end, start, Ignored, Longest, Some(154)),
+            (end, start, Ignored, Shortest, Some(74)),
+            (start, middle, Strict, Shortest, Some(5)),
+            (start, middle, Strict, Longest, Some(5)),
+        ];
+        for (from, to, slopes, goal, expected) in cases {
+            let hike = hike(&chart, from, to, slopes, goal);
+            assert_eq!(hike.as_ref().map(|hike| hike.length), expected);
+            if let (Some(hike), false) = (hike, matches!(slopes, Uphill(10))) {
+                assert_eq!(hike.route.first(), Some(&from));
+                assert_eq!(hike.route.last(), Some(&to));
+                assert_eq!(hike.route.len() as u32, hike.length + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn test_day23_shortest_many_junctions() {
+        // a lattice of corridors crossing on every odd row and column, with
+        // more junctions than fit in a single word
+        let data: Vec<String> = (0..23)
+            .map(|row| {
+                (0..23)
+                    .map(|col| {
+                        let open = match row {
+                            0 => col == 1,
+                            22 => col == 21,
+                            _ => (row % 2 == 1 || col % 2 == 1) && (1..22).contains(&col),
+                        };
+                        if open {
+                            '.'
+                        } else {
+                            '#'
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let (chart, start, end) = get_state::<23>(&data.join("\n"));
+        assert!(
+            JunctionGraph::new(&chart, start, end, SlopeMode::Strict)
+                .junctions
+                .len()
+                > 64
+        );
+        let hike = hike(&chart, start, end, SlopeMode::Strict, Goal::Shortest).unwrap();
+        assert_eq!(hike.length, 22 + 20);
+    }
+
+    #[test]
+    fn test_day23_longest_many_junctions() {
+        // a single trail winding back and forth, with dead end stubs off
+        // either side making well over 64 junctions
+        let data: Vec<String> = (0..23)
+            .map(|row| {
+                (0..23)
+                    .map(|col| {
+                        let open = match (row, row % 4) {
+                            (0 | 22, _) => col == 1,
+                            (_, 1) => (1..22).contains(&col),
+                            // the bend down to the next row of the trail
+                            _ if col == [21, 1][(row - 1) / 4 % 2] && row % 4 != 1 => true,
+                            (_, 2) => col % 2 == 1 && (3..20).contains(&col),
+                            (_, 0) => col % 2 == 0 && (4..19).contains(&col),
+                            _ => false,
+                        };
+                        if open {
+                            '.'
+                        } else {
+                            '#'
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        let (chart, start, end) = get_state::<23>(&data.join("\n"));
+        assert!(
+            JunctionGraph::new(&chart, start, end, SlopeMode::Ignored)
+                .junctions
+                .len()
+                > 64
+        );
+        let hike = hike(&chart, start, end, SlopeMode::Ignored, Goal::Longest).unwrap();
+        assert_eq!(hike.length, 6 * 21 + 5 * 3 + 1);
+    }
+
     #[test]
     fn test_day23_p1() {
         assert_eq!(run_day23_p1(), 2070);
     }
 
     #[test]
-    #[ignore]
     fn test_day23_p2() {
-        // takes almost 2 minutes
         assert_eq!(run_day23_p2(), 6498);
     }
 }
@@ -1,11 +1,13 @@
 //! Day 24: Never Tell Me The Odds
 // use rayon::prelude::*;
+use aoc_2023::rational::Rational;
 use itertools::Itertools;
-use num::integer::lcm;
-use num::Integer;
+use num::{BigRational, Signed, Zero};
 use std::env;
+use std::fmt;
 use std::fs::read_to_string;
 use std::marker::PhantomData;
+use std::ops::{Add, Mul, Neg, Sub};
 use std::time::Instant;
 
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord)]
@@ -38,6 +40,71 @@ impl<Quantity> From<&str> for Vector<Quantity> {
     }
 }
 
+impl<Quantity> Vector<Quantity> {
+    fn new(x: i128, y: i128, z: i128) -> Self {
+        Self {
+            x,
+            y,
+            z,
+            quantity: PhantomData,
+        }
+    }
+
+    fn to_array(&self) -> [i128; 3] {
+        [self.x, self.y, self.z]
+    }
+
+    fn scale(self, factor: i128) -> Self {
+        Self::new(self.x * factor, self.y * factor, self.z * factor)
+    }
+
+    fn dot<Other>(&self, other: &Vector<Other>) -> i128 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// Z component of the cross product, i.e. the cross product of the XY projections
+    fn cross_xy<Other>(&self, other: &Vector<Other>) -> i128 {
+        self.x * other.y - self.y * other.x
+    }
+}
+
+impl<Quantity> Add for Vector<Quantity> {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Self::new(self.x + other.x, self.y + other.y, self.z + other.z)
+    }
+}
+
+impl<Quantity> Sub for Vector<Quantity> {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        Self::new(self.x - other.x, self.y - other.y, self.z - other.z)
+    }
+}
+
+impl<Quantity> Neg for Vector<Quantity> {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.scale(-1)
+    }
+}
+
+/// A velocity times a time is a distance travelled
+impl Mul<i128> for Vector<Velocity> {
+    type Output = Vector<Position>;
+
+    fn mul(self, time: i128) -> Vector<Position> {
+        Vector::new(self.x * time, self.y * time, self.z * time)
+    }
+}
+
+fn cross([ax, ay, az]: [i128; 3], [bx, by, bz]: [i128; 3]) -> [i128; 3] {
+    [ay * bz - az * by, az * bx - ax * bz, ax * by - ay * bx]
+}
+
 #[derive(Debug, Clone, Copy, Eq, Hash, PartialEq, PartialOrd, Ord)]
 struct Hailstone {
     pos: Vector<Position>,
@@ -54,89 +121,55 @@ impl From<&str> for Hailstone {
     }
 }
 
-impl Hailstone {
-    fn intersection(&self, other: &Self) -> [Rational; 4] {
-        fn intersection_x(
-            px1: i128,
-            py1: i128,
-            px2: i128,
-            py2: i128,
-            vx1: i128,
-            vy1: i128,
-            vx2: i128,
-            vy2: i128,
-        ) -> Rational {
-            let denom = vx2 * vy1 - vx1 * vy2;
-            let numer = vx2 * vy1 * px1 - vx1 * vy2 * px2 + vx1 * vx2 * (py2 - py1);
-            Rational { numer, denom }
-        }
-        let xi = intersection_x(
-            self.pos.x,
-            self.pos.y,
-            other.pos.x,
-            other.pos.y,
-            self.vel.x,
-            self.vel.y,
-            other.vel.x,
-            other.vel.y,
-        );
-        let yi = intersection_x(
-            self.pos.y,
-            self.pos.x,
-            other.pos.y,
-            other.pos.x,
-            self.vel.y,
-            self.vel.x,
-            other.vel.y,
-            other.vel.x,
-        );
-        let t1 = Rational {
-            numer: xi.numer - xi.denom * self.pos.x,
-            denom: xi.denom * self.vel.x,
-        };
-        let t2 = Rational {
-            numer: xi.numer - xi.denom * other.pos.x,
-            denom: xi.denom * other.vel.x,
-        };
-        [xi, yi, t1, t2]
-    }
-}
-
-#[derive(Debug)]
-struct Rational {
-    numer: i128,
-    denom: i128,
+/// Where the paths of two hailstones cross in the XY plane,
+/// and the times at which each of them passes through that point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Intersection {
+    x: Rational,
+    y: Rational,
+    t1: Rational,
+    t2: Rational,
 }
 
-impl Rational {
-    fn in_bounds(&self, lower: i128, upper: i128) -> bool {
-        let Rational { numer, denom } = self;
-        // make sure the denominator is non-negative
-        let numer = numer * denom.signum();
-        let denom = denom * denom.signum();
-        (numer >= lower * denom) && (numer <= upper * denom)
-    }
-
-    fn signum(&self) -> i128 {
-        self.numer.signum() * self.denom.signum()
+impl fmt::Display for Intersection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "x={}, y={} at t={} and t={}",
+            self.x, self.y, self.t1, self.t2
+        )
     }
 }
 
-impl TryFrom<Rational> for i128 {
-    type Error = &'static str;
+impl Hailstone {
+    #[cfg(test)]
+    fn position_at(&self, time: i128) -> Vector<Position> {
+        self.pos + self.vel * time
+    }
 
-    fn try_from(value: Rational) -> Result<Self, Self::Error> {
-        let (quotient, remainder) = (value.numer / value.denom, value.numer % value.denom);
-        if remainder != 0 {
-            Err("Rational is not an integer")
-        } else {
-            Ok(quotient)
+    /// None if the paths are parallel in the XY plane
+    fn intersection(&self, other: &Self) -> Option<Intersection> {
+        // solve pos1 + vel1 * t1 = pos2 + vel2 * t2 by crossing
+        // both sides with vel2 (to find t1) or vel1 (to find t2)
+        let denom = self.vel.cross_xy(&other.vel);
+        if denom == 0 {
+            return None;
         }
+        let gap = other.pos - self.pos;
+        let t1 = Rational::new(gap.cross_xy(&other.vel), denom);
+        let t2 = Rational::new(gap.cross_xy(&self.vel), denom);
+        Some(Intersection {
+            x: Rational::from(self.pos.x) + Rational::from(self.vel.x) * t1,
+            y: Rational::from(self.pos.y) + Rational::from(self.vel.y) * t1,
+            t1,
+            t2,
+        })
     }
 }
 
 fn day24_p1(data: &str, lower: i128, upper: i128) -> usize {
     let hailstones: Vec<Hailstone> = data.lines().map(Hailstone::from).collect();
+    let bounds = Rational::from(lower)..=Rational::from(upper);
     hailstones
         .iter()
         .enumerate()
@@ -144,19 +177,19 @@ fn day24_p1(data: &str, lower: i128, upper: i128) -> usize {
             hailstones
                 .iter()
                 .skip(ii + 1)
-                .filter(|hailstone2| {
-                    let [ix, iy, t1, t2] = hailstone1.intersection(hailstone2);
-                    if ix.denom == 0 {
-                        if ix.numer == 0 {
+                .filter(|hailstone2| match hailstone1.intersection(hailstone2) {
+                    None => {
+                        let gap = hailstone2.pos - hailstone1.pos;
+                        if gap.cross_xy(&hailstone1.vel) == 0 {
                             panic!("Handle this case");
-                        } else {
-                            false
                         }
-                    } else {
-                        (t1.signum() > 0)
-                            && (t2.signum() > 0)
-                            && ix.in_bounds(lower, upper)
-                            && iy.in_bounds(lower, upper)
+                        false
+                    }
+                    Some(cross) => {
+                        (cross.t1 > Rational::ZERO)
+                            && (cross.t2 > Rational::ZERO)
+                            && bounds.contains(&cross.x)
+                            && bounds.contains(&cross.y)
                     }
                 })
                 .count()
@@ -164,95 +197,270 @@ fn day24_p1(data: &str, lower: i128, upper: i128) -> usize {
         .sum()
 }
 
-/// Finds the solution to the Chinese Remainder Theorem (CRT) if it exists,
-/// otherwise return None. Mod values (ni) do not need to be coprime.
-/// Solves the system of equations: xi = ai mod ni for xi,ai,ni integers.
-fn chinese_remainder_theorem(values: &[(i128, i128)]) -> Option<(i128, i128)> {
-    // I think there is a bug in this implementation as it sometimes returns
-    // values too large or None when its not supposed too.
-    match values.len() {
-        0 => panic!("values is empty"),
-        1 => Some((values[0].0 % values[0].1, values[0].1)),
-        2 => {
-            let [(a1, n1), (a2, n2)]: [(i128, i128); 2] = values.try_into().unwrap();
-            let egcd = n1.extended_gcd(&n2);
-            if (a1 - a2) % egcd.gcd != 0 {
-                return None;
-            }
-            let lambda = (a1 - a2) / egcd.gcd;
-            let m = lcm(n1, n2);
-            let mut x = (a1 - n1 * lambda * egcd.x) % m;
-            if x < 0 {
-                x += m;
-            }
-            Some((x, m))
+/// When two hailstones are nearest each other in 3D, over all time (past included)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ClosestApproach {
+    time: Rational,
+    /// kept squared so it stays exact
+    distance_squared: Rational,
+}
+
+impl ClosestApproach {
+    fn distance(&self) -> f64 {
+        self.distance_squared.to_f64().sqrt()
+    }
+
+    fn is_collision(&self) -> bool {
+        self.distance_squared == Rational::ZERO
+    }
+}
+
+impl Hailstone {
+    fn position_at_exact(&self, time: Rational) -> [Rational; 3] {
+        let [px, py, pz] = self.pos.to_array().map(Rational::from);
+        let [vx, vy, vz] = self.vel.to_array().map(Rational::from);
+        [px + vx * time, py + vy * time, pz + vz * time]
+    }
+
+    /// The gap between the two is `gap + closing * t`, and its squared length is a
+    /// parabola in t with its minimum at `t = -(gap . closing) / (closing . closing)`.
+    /// Hailstones moving in parallel are always the same distance apart, so
+    /// their closest approach is reported at t = 0.
+    fn closest_approach(&self, other: &Self) -> ClosestApproach {
+        let gap = other.pos - self.pos;
+        let closing = other.vel - self.vel;
+        let speed_squared = closing.dot(&closing);
+        if speed_squared == 0 {
+            return ClosestApproach {
+                time: Rational::ZERO,
+                distance_squared: Rational::from(gap.dot(&gap)),
+            };
         }
-        n => {
-            let half = n / 2;
-            let a = chinese_remainder_theorem(&values[..half])?;
-            let b = chinese_remainder_theorem(&values[half..])?;
-            chinese_remainder_theorem(&[a, b])
+        let gap_closing = gap.dot(&closing);
+        ClosestApproach {
+            time: Rational::new(-gap_closing, speed_squared),
+            distance_squared: Rational::from(gap.dot(&gap))
+                - Rational::new(gap_closing * gap_closing, speed_squared),
         }
     }
+
+    /// Time at which the two hailstones are in exactly the same place, if ever
+    fn collision(&self, other: &Self) -> Option<Rational> {
+        let closest = self.closest_approach(other);
+        closest.is_collision().then_some(closest.time)
+    }
 }
 
-fn day24_p2(data: &str, take: usize) -> i128 {
-    // due to a bug (I assume in the CRT implementation), some values of take here
-    // give wrong answers or never return. A take value of 45 works on the input.
-    let hailstones: Vec<Hailstone> = data.lines().map(Hailstone::from).take(take).collect();
-    // we only need the sum of the rocks x,y,z so we can just do the same to the hailstones
-    // and then only have to compute once. Yay, linearity.
-    let ak: Vec<(i128, i128)> = hailstones
+/// Every pair of hailstones (by index) that actually collides within
+/// `start..=end`, along with the time of the collision, in time order
+fn collisions_within(
+    hailstones: &[Hailstone],
+    start: Rational,
+    end: Rational,
+) -> Vec<(usize, usize, Rational)> {
+    let mut collisions: Vec<(usize, usize, Rational)> = hailstones
         .iter()
-        .map(|hs| {
-            (
-                hs.pos.x + hs.pos.y + hs.pos.z,
-                hs.vel.x + hs.vel.y + hs.vel.z,
-            )
+        .enumerate()
+        .tuple_combinations()
+        .filter_map(|((ii, hailstone1), (jj, hailstone2))| {
+            let time = hailstone1.collision(hailstone2)?;
+            (start <= time && time <= end).then_some((ii, jj, time))
         })
         .collect();
-    // The CRT doesn't give us a good way to handle estimating our vx+vy+vz, so we just
-    // brute force this one parameter.
-    'outer: for vel in (0..).interleave((1..).map(|x| -x)) {
-        let ak_nk: Vec<(i128, i128)> = ak.iter().map(|(ai, ni)| (*ai, *ni - vel)).collect();
-        // x0 = ak + nk*t
-        if ak_nk.iter().any(|(_, ni)| *ni == 0) {
-            // if the net velocity (nk) is ever zero, then our position (x0)
-            // must equal the hailstones initial position (ak).
-            // This doesn't happen in practices though, logic is in commit history.
+    collisions.sort_by_key(|&(ii, jj, time)| (time, ii, jj));
+    collisions
+}
+
+pub fn run_day24_collisions(start: i128, end: i128) {
+    let filename = "data/day_24.txt";
+    let data = read_to_string(filename).unwrap();
+    let hailstones: Vec<Hailstone> = data.lines().map(Hailstone::from).collect();
+    let window = (Rational::from(start), Rational::from(end));
+    for (ii, jj, time) in collisions_within(&hailstones, window.0, window.1) {
+        let [x, y, z] = hailstones[ii].position_at_exact(time);
+        println!("hailstones {ii} and {jj} collide at t={time} at ({x}, {y}, {z})");
+    }
+    let closest = hailstones
+        .iter()
+        .enumerate()
+        .tuple_combinations()
+        .map(|((ii, hailstone1), (jj, hailstone2))| {
+            (ii, jj, hailstone1.closest_approach(hailstone2))
+        })
+        .filter(|(_, _, closest)| window.0 <= closest.time && closest.time <= window.1)
+        .min_by_key(|(_, _, closest)| closest.distance_squared);
+    if let Some((ii, jj, closest)) = closest {
+        println!(
+            "closest approach is hailstones {ii} and {jj}, {:.3} apart at t={}",
+            closest.distance(),
+            closest.time
+        );
+    }
+}
+
+/// Why a system of equations has no single solution
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SolveError {
+    /// Some unknowns are free to take any value
+    Underdetermined,
+    /// The equations contradict each other
+    Inconsistent,
+}
+
+/// Row reduces the augmented matrix `[A | b]` with exact arithmetic to solve `A x = b`.
+/// There can be more equations than unknowns, as long as they all agree.
+fn solve_exact(mut rows: Vec<Vec<BigRational>>) -> Result<Vec<BigRational>, SolveError> {
+    let unknowns = rows[0].len() - 1;
+    for col in 0..unknowns {
+        let Some(pivot) = (col..rows.len()).find(|&row| !rows[row][col].is_zero()) else {
+            return Err(SolveError::Underdetermined);
+        };
+        rows.swap(col, pivot);
+        let scale = rows[col][col].clone();
+        rows[col].iter_mut().for_each(|val| *val /= &scale);
+        let pivot_row = rows[col].clone();
+        for (idx, row) in rows.iter_mut().enumerate() {
+            if idx == col || row[col].is_zero() {
+                continue;
+            }
+            let factor = row[col].clone();
+            for (val, pivot_val) in row.iter_mut().zip(pivot_row.iter()) {
+                *val -= &factor * pivot_val;
+            }
+        }
+    }
+    if rows[unknowns..].iter().any(|row| !row[unknowns].is_zero()) {
+        return Err(SolveError::Inconsistent);
+    }
+    Ok(rows
+        .into_iter()
+        .take(unknowns)
+        .map(|row| row[unknowns].clone())
+        .collect())
+}
+
+/// Why no rock trajectory could be found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ThrowError {
+    /// Fewer than three hailstones, or too many of them moving in step
+    TooFewHailstones,
+    /// The hailstones disagree about where the rock has to be
+    NoSolution,
+    /// The only solution isn't on whole numbers
+    NotIntegral,
+    /// The solution doesn't fit in an `i128`
+    Overflow,
+    /// The rock never closes in on one of the hailstones
+    Alongside,
+    /// The rock passes by one of the hailstones
+    Misses,
+    /// The rock could only have hit one of the hailstones before we throw it
+    InThePast,
+}
+
+/// The rock we throw, and the time at which it hits each hailstone
+#[derive(Debug, PartialEq)]
+struct Throw {
+    rock: Hailstone,
+    hits: Vec<i128>,
+}
+
+/// Finds the one rock trajectory that hits every hailstone.
+///
+/// For the rock (P, V) to hit hailstone i at some time, (P - p_i) and (V - v_i)
+/// have to be parallel, so (P - p_i) x (V - v_i) = 0. Expanded, the only
+/// non-linear term is P x V, which is the same for every hailstone, so
+/// subtracting the equations for two hailstones i and j leaves three linear
+/// equations:
+///     P x (v_j - v_i) + (p_j - p_i) x V = p_j x v_j - p_i x v_i
+/// A few pairs give enough equations to solve for the 6 unknowns exactly.
+fn throw_rock(hailstones: &[Hailstone]) -> Result<Throw, ThrowError> {
+    let [first, rest @ ..] = hailstones else {
+        return Err(ThrowError::TooFewHailstones);
+    };
+    let (p0, v0) = (first.pos.to_array(), first.vel.to_array());
+    let mut rows = vec![];
+    let mut solution = Err(ThrowError::TooFewHailstones);
+    for other in rest {
+        let (pj, vj) = (other.pos.to_array(), other.vel.to_array());
+        let [wx, wy, wz] = (other.vel - first.vel).to_array();
+        let [dx, dy, dz] = (other.pos - first.pos).to_array();
+        let (cj, c0) = (cross(pj, vj), cross(p0, v0));
+        // unknowns are [Px, Py, Pz, Vx, Vy, Vz]
+        let new_rows = [
+            [0, wz, -wy, 0, -dz, dy, cj[0] - c0[0]],
+            [-wz, 0, wx, dz, 0, -dx, cj[1] - c0[1]],
+            [wy, -wx, 0, -dy, dx, 0, cj[2] - c0[2]],
+        ];
+        rows.extend(new_rows.iter().map(|row| {
+            row.iter()
+                .map(|&val| BigRational::from_integer(val.into()))
+                .collect::<Vec<_>>()
+        }));
+        if rows.len() < 6 {
             continue;
         }
-        let Some((x0, m)) = chinese_remainder_theorem(&ak_nk) else {
-            continue 'outer;
-        };
-        // println!("{} mod {} @ {}", x0, m, vel);
-
-        // we have found a solution that satisfies the CRT, but we need to verify
-        // that the time points are all positive (the hailstones future).
-        let mut ts: Vec<i128> = ak_nk
-            .iter()
-            .filter(|(_, ni)| *ni != 0)
-            .map(|(ai, ni)| (x0 - ai) / ni)
-            .collect();
-        // we limit our search to [-1000, 1000] to avoid getting stuck here forever
-        let mut cs = (1..1000).interleave((1..1000).map(|x| -x));
-        let mut c = 0;
-        while ts.iter().any(|&t| t <= 0) {
-            c = if let Some(c) = cs.next() {
-                c
-            } else {
-                continue 'outer;
-            };
-            ts = ak_nk
-                .iter()
-                .filter(|(_, ni)| *ni != 0)
-                .map(|(ai, ni)| (x0 + c * m - ai) / ni)
-                .collect();
+        match solve_exact(rows.clone()) {
+            Ok(values) => {
+                solution = Ok(values);
+                break;
+            }
+            Err(SolveError::Inconsistent) => return Err(ThrowError::NoSolution),
+            Err(SolveError::Underdetermined) => (),
         }
-        let x0 = x0 + c * m;
-        return x0;
     }
-    unreachable!();
+    let solution = solution?;
+    let rock_pos = &solution[..3];
+    let rock_vel = &solution[3..];
+
+    // make sure every hailstone (not only the ones used above) is hit in the
+    // future, before worrying about whether the numbers are whole
+    let hits = hailstones
+        .iter()
+        .map(|hailstone| {
+            let pos = hailstone
+                .pos
+                .to_array()
+                .map(|val| BigRational::from_integer(val.into()));
+            let vel = hailstone
+                .vel
+                .to_array()
+                .map(|val| BigRational::from_integer(val.into()));
+            let Some(axis) = (0..3).find(|&axis| rock_vel[axis] != vel[axis]) else {
+                return Err(ThrowError::Alongside);
+            };
+            let time = (&pos[axis] - &rock_pos[axis]) / (&rock_vel[axis] - &vel[axis]);
+            if (0..3).any(|axis| {
+                &rock_pos[axis] + &rock_vel[axis] * &time != &pos[axis] + &vel[axis] * &time
+            }) {
+                return Err(ThrowError::Misses);
+            }
+            Ok(time)
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if hits.iter().any(|time| !time.is_positive()) {
+        return Err(ThrowError::InThePast);
+    }
+
+    let to_i128 = |val: &BigRational| {
+        if !val.is_integer() {
+            return Err(ThrowError::NotIntegral);
+        }
+        i128::try_from(val.to_integer()).map_err(|_| ThrowError::Overflow)
+    };
+    let solution: Vec<i128> = solution.iter().map(to_i128).collect::<Result<_, _>>()?;
+    let hits = hits.iter().map(to_i128).collect::<Result<_, _>>()?;
+    let rock = Hailstone {
+        pos: Vector::new(solution[0], solution[1], solution[2]),
+        vel: Vector::new(solution[3], solution[4], solution[5]),
+    };
+    Ok(Throw { rock, hits })
+}
+
+fn day24_p2(data: &str) -> i128 {
+    let hailstones: Vec<Hailstone> = data.lines().map(Hailstone::from).collect();
+    let Throw { rock, .. } = throw_rock(&hailstones).unwrap();
+    rock.pos.x + rock.pos.y + rock.pos.z
 }
 
 pub fn run_day24_p1() -> usize {
@@ -264,13 +472,19 @@ pub fn run_day24_p1() -> usize {
 pub fn run_day24_p2() -> i128 {
     let filename = "data/day_24.txt";
     let data = read_to_string(filename).unwrap();
-    // for ii in 1..301 {
-    //     println!("{:?}: {:?}", ii, day24_p2(&data, ii));
-    // }
-    day24_p2(&data, 45)
+    day24_p2(&data)
 }
 
 fn main() {
+    if env::args().nth(1).as_deref() == Some("collisions") {
+        // `day24 collisions [start end]` lists hailstones that hit each other
+        let start = env::args().nth(2).map_or(0, |arg| arg.parse().unwrap());
+        let end = env::args()
+            .nth(3)
+            .map_or(i64::MAX as i128, |arg| arg.parse().unwrap());
+        run_day24_collisions(start, end);
+        return;
+    }
     let part1 = if let Some(arg1) = env::args().nth(1) {
         arg1.parse().unwrap_or(1) == 1
     } else {
@@ -307,62 +521,104 @@ mod test {
 
     #[test]
     fn test_day24_p2_example() {
-        assert_eq!(day24_p2(EXAMPLE, 100), 47);
+        assert_eq!(day24_p2(EXAMPLE), 47);
     }
 
     #[test]
-    fn test_day24_p1() {
-        assert_eq!(run_day24_p1(), 18098);
+    fn test_day24_throw_rock() {
+        let hailstones: Vec<Hailstone> = EXAMPLE.lines().map(Hailstone::from).collect();
+        let throw = throw_rock(&hailstones).unwrap();
+        assert_eq!(throw.rock, Hailstone::from("24, 13, 10 @ -3, 1, 2"));
+        assert_eq!(throw.hits, vec![5, 3, 4, 6, 1]);
     }
 
     #[test]
-    fn test_day24_p2() {
-        assert_eq!(run_day24_p2(), 886858737029295);
+    fn test_day24_throw_rock_errors() {
+        let hailstones: Vec<Hailstone> = EXAMPLE.lines().map(Hailstone::from).collect();
+        assert_eq!(
+            throw_rock(&hailstones[..2]),
+            Err(ThrowError::TooFewHailstones)
+        );
+        // a hailstone that the rock from the example only meets in the past
+        let mut past = hailstones.clone();
+        past.push(Hailstone::from("27, 12, 8 @ 0, 0, 0"));
+        assert_eq!(throw_rock(&past), Err(ThrowError::InThePast));
+        // and one that it misses entirely
+        let mut miss = hailstones.clone();
+        miss.push(Hailstone::from("0, 0, 0 @ 1, 1, 1"));
+        assert_eq!(throw_rock(&miss), Err(ThrowError::Misses));
+        // wherever it is, even when it's one of the hailstones the rock is solved from
+        for _ in 0..miss.len() {
+            miss.rotate_right(1);
+            assert_eq!(throw_rock(&miss), Err(ThrowError::Misses));
+        }
     }
 
     #[test]
-    fn test_crt() {
-        let values = [(2, 3), (3, 5), (2, 7)];
-        let (x, m) = chinese_remainder_theorem(&values).unwrap();
-        assert_eq!(x, 23);
-        assert_eq!(m, 105);
+    fn test_day24_intersection() {
+        let hailstones: Vec<Hailstone> = EXAMPLE.lines().map(Hailstone::from).collect();
+        let cross = hailstones[0].intersection(&hailstones[1]).unwrap();
+        assert_eq!(cross.to_string(), "x=43/3, y=46/3 at t=7/3 and t=11/3");
+        let cross = hailstones[0].intersection(&hailstones[4]).unwrap();
+        assert!(cross.t1 < Rational::ZERO);
+        assert_eq!(hailstones[1].intersection(&hailstones[2]), None);
     }
 
     #[test]
-    fn test_crt_non_coprime() {
-        let values = [(1, 2), (1, 3), (1, 4), (1, 5), (1, 6)];
-        let (x, m) = chinese_remainder_theorem(&values).unwrap();
-        assert_eq!(x, 1);
-        assert_eq!(m, 60);
+    fn test_day24_vector_ops() {
+        let hailstone = Hailstone::from("19, 13, 30 @ -2, 1, -2");
+        assert_eq!(hailstone.position_at(5), Vector::new(9, 18, 20));
+        assert_eq!(hailstone.pos - hailstone.pos, Vector::new(0, 0, 0));
+        assert_eq!(-hailstone.vel, hailstone.vel.scale(-1));
+        assert_eq!(hailstone.vel + hailstone.vel, hailstone.vel.scale(2));
     }
 
     #[test]
-    fn test_crt_p2() {
-        // vel = -3
-        let values = [
-            (19, -2 + 3),
-            (18, -1 + 3),
-            (20, -2 + 3),
-            (12, -1 + 3),
-            (20, 1 + 3),
-        ];
-        let (x0, m) = chinese_remainder_theorem(&values).unwrap();
-        let mut ts: Vec<i128> = values
-            .iter()
-            .filter(|(_, ni)| *ni != 0)
-            .map(|(ai, ni)| (x0 - ai) / ni)
+    fn test_day24_closest_approach() {
+        let a = Hailstone::from("0, 0, 0 @ 1, 0, 0");
+        let b = Hailstone::from("10, 3, 4 @ -1, 0, 0");
+        let closest = a.closest_approach(&b);
+        assert_eq!(closest.time, Rational::from(5));
+        assert_eq!(closest.distance_squared, Rational::from(25));
+        assert_eq!(closest.distance(), 5.0);
+        assert_eq!(a.collision(&b), None);
+        // same velocity, so always the same distance apart
+        let c = Hailstone::from("0, 2, 0 @ 1, 0, 0");
+        assert_eq!(a.closest_approach(&c).distance_squared, Rational::from(4));
+        // closest approach in the past, at a fractional time
+        let d = Hailstone::from("0, 1, 0 @ 1, 2, 0");
+        let closest = a.closest_approach(&d);
+        assert_eq!(closest.time, Rational::new(-1, 2));
+        assert_eq!(closest.distance_squared, Rational::ZERO);
+        assert_eq!(a.collision(&d), Some(Rational::new(-1, 2)));
+        assert_eq!(
+            d.position_at_exact(Rational::new(-1, 2)),
+            a.position_at_exact(Rational::new(-1, 2))
+        );
+    }
+
+    #[test]
+    fn test_day24_collisions_within() {
+        // every example hailstone collides with the thrown rock
+        let mut hailstones: Vec<Hailstone> = EXAMPLE.lines().map(Hailstone::from).collect();
+        hailstones.push(Hailstone::from("24, 13, 10 @ -3, 1, 2"));
+        let collisions = collisions_within(&hailstones, Rational::ZERO, Rational::from(100));
+        let expected: Vec<(usize, usize, Rational)> = [(4, 1), (1, 3), (2, 4), (0, 5), (3, 6)]
+            .into_iter()
+            .map(|(ii, time)| (ii, 5, Rational::from(time)))
             .collect();
-        let mut cs = (1..).interleave((1..).map(|x| -x));
-        let mut c = 0;
-        while ts.iter().any(|&t| t <= 0) {
-            c = cs.next().unwrap();
-            ts = values
-                .iter()
-                .filter(|(_, ni)| *ni != 0)
-                .map(|(ai, ni)| (x0 + c * m - ai) / ni)
-                .collect();
-        }
-        let x = x0 + c * m;
-        assert_eq!(x, 24);
+        assert_eq!(collisions, expected);
+        let collisions = collisions_within(&hailstones, Rational::from(2), Rational::from(4));
+        assert_eq!(collisions.len(), 2);
+    }
+
+    #[test]
+    fn test_day24_p1() {
+        assert_eq!(run_day24_p1(), 18098);
+    }
+
+    #[test]
+    fn test_day24_p2() {
+        assert_eq!(run_day24_p2(), 886858737029295);
     }
 }
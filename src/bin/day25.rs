@@ -1,61 +1,219 @@
 //! Day 25: Snowverload
-use nalgebra::base::*;
-use std::collections::HashMap;
+use aoc_2023::dot;
+use aoc_2023::graph::{CsrGraph, Interner};
+use std::collections::VecDeque;
+use std::env;
 use std::fs::read_to_string;
 use std::time::Instant;
 
-fn day25_p1<const N: usize>(data: &str) -> usize {
-    let mut adj_matrix = Box::new([[0u8; N]; N]);
-    let mut index = 0usize;
-    let mut index_map = HashMap::new();
+/// The wires to cut and the sizes of the two groups of components left behind
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MinCut {
+    pub wires: Vec<(String, String)>,
+    pub sizes: (usize, usize),
+}
+
+/// Component names alongside the wires between them, in CSR form so that
+/// large diagrams only cost memory in proportion to the number of wires
+pub struct Wiring {
+    names: Interner,
+    graph: CsrGraph,
+}
+
+impl Wiring {
+    /// Graphviz DOT of the wiring diagram. Best rendered with `neato`
+    /// or `sfdp`, where the three wires to cut tend to stand out.
+    pub fn to_dot(&self) -> dot::Graph {
+        let mut dot = dot::Graph::undirected("wiring");
+        for node in self.graph.nodes() {
+            for &next in self.graph.neighbors(node) {
+                // each wire is stored both ways, so only write it from its lower end
+                if node < next {
+                    dot.edge(self.names.label(node), self.names.label(next), &[]);
+                }
+            }
+        }
+        dot
+    }
+}
+
+fn parse_wiring(data: &str) -> Wiring {
+    let mut names = Interner::new();
+    let mut edges = vec![];
     for line in data.lines() {
         let (left, rights) = line.split_once(": ").unwrap();
-        let left_i = *index_map.entry(left).or_insert_with(|| {
-            let i = index;
-            index += 1;
-            i
-        });
+        let left = names.intern(left);
         for right in rights.split(' ') {
-            let right_i = *index_map.entry(right).or_insert_with(|| {
-                let i = index;
-                index += 1;
-                i
-            });
-            adj_matrix[left_i][right_i] = 1;
-            adj_matrix[right_i][left_i] = 1;
+            edges.push((left, names.intern(right)));
         }
     }
-    let degree: Vec<u8> = adj_matrix.iter().map(|row| row.iter().sum()).collect();
-
-    // I tried to avoid heavy dependencies, but I don't know how to solve this one outside
-    // of an eigen-decomposition of the Laplacian. I'm not using the fact that I should
-    // only cut 3 edges. Seems like there is a better way, but this is plenty fast in
-    // release mode, even though the matrix is not static.
-    let mut lap_matrix = DMatrix::from_fn(N, N, |row, col| -(adj_matrix[row][col] as f32));
-    for (ii, val) in degree.into_iter().enumerate() {
-        lap_matrix[(ii, ii)] = val as f32;
-    }
-    let edecom = lap_matrix.symmetric_eigen();
-    let mut eigen: Vec<(_, _)> = edecom
-        .eigenvalues
-        .into_iter()
-        .zip(edecom.eigenvectors.column_iter())
+    let graph = CsrGraph::undirected(names.len(), &edges);
+    Wiring { names, graph }
+}
+
+/// Pushes up to `limit` edge-disjoint paths from `source` to `sink`, finding
+/// each by BFS in the residual graph (Edmonds-Karp with unit capacities).
+/// Returns how many paths were found along with which nodes the last BFS could
+/// reach. When fewer than `limit` paths exist that reachable set is the
+/// source side of a minimum cut between the two.
+fn edge_disjoint_paths(
+    graph: &CsrGraph,
+    source: u32,
+    sink: u32,
+    limit: usize,
+) -> (usize, Vec<bool>) {
+    // whether each arc is carrying a unit of flow
+    let mut flow = vec![false; graph.arc_count()];
+    // arc used to reach each node
+    let mut parent = vec![usize::MAX; graph.len()];
+    let mut reached = vec![false; graph.len()];
+    let mut queue = VecDeque::new();
+    for paths in 0..limit {
+        reached.fill(false);
+        reached[source as usize] = true;
+        queue.clear();
+        queue.push_back(source);
+        while let Some(node) = queue.pop_front() {
+            if node == sink {
+                break;
+            }
+            for arc in graph.arcs(node) {
+                let next = graph.target(arc);
+                if !reached[next as usize] && !flow[arc] {
+                    reached[next as usize] = true;
+                    parent[next as usize] = arc;
+                    queue.push_back(next);
+                }
+            }
+        }
+        if !reached[sink as usize] {
+            return (paths, reached);
+        }
+        let mut node = sink;
+        while node != source {
+            let arc = parent[node as usize];
+            let back = graph.reverse(arc);
+            // flow back along a used wire cancels it out
+            if flow[back] {
+                flow[back] = false;
+            } else {
+                flow[arc] = true;
+            }
+            node = graph.target(back);
+        }
+    }
+    (limit, reached)
+}
+
+/// Whether the nodes with `side[node] == which` form a single connected
+/// group without leaving that side
+fn is_connected_within(graph: &CsrGraph, side: &[bool], which: bool) -> bool {
+    let Some(start) = graph.nodes().find(|&node| side[node as usize] == which) else {
+        return false;
+    };
+    let mut seen = vec![false; graph.len()];
+    seen[start as usize] = true;
+    let mut stack = vec![start];
+    let mut count = 0;
+    while let Some(node) = stack.pop() {
+        count += 1;
+        for &next in graph.neighbors(node) {
+            if side[next as usize] == which && !seen[next as usize] {
+                seen[next as usize] = true;
+                stack.push(next);
+            }
+        }
+    }
+    count == side.iter().filter(|&&s| s == which).count()
+}
+
+/// Finds the `expected` wires whose removal splits the diagram in two.
+///
+/// Every cut separates the first component from some other one, so a
+/// bounded max flow from the first component to each of the others finds
+/// the global minimum cut in O(components * `expected` * wires). Any pair
+/// joined by fewer than `expected` disjoint paths means there's a smaller
+/// cut, which is an error, as is there being no cut of `expected` wires.
+pub fn min_cut(wiring: &Wiring, expected: usize) -> Result<MinCut, String> {
+    let Wiring { names, graph } = wiring;
+    if graph.len() < 2 {
+        return Err("Need at least two components to cut".to_string());
+    }
+    // a component with too few wires is a smaller cut all by itself
+    if let Some(node) = graph
+        .nodes()
+        .find(|&node| graph.neighbors(node).len() < expected)
+    {
+        return Err(format!(
+            "{} has only {} wires, expected the minimum cut to be {expected}",
+            names.label(node),
+            graph.neighbors(node).len()
+        ));
+    }
+    let source = 0;
+    let mut side = None;
+    for sink in graph.nodes().skip(1) {
+        let (paths, reached) = edge_disjoint_paths(graph, source, sink, expected + 1);
+        if paths < expected {
+            let (source, sink) = (names.label(source), names.label(sink));
+            return Err(format!(
+                "{source} and {sink} are joined by only {paths} disjoint paths, \
+                 expected the minimum cut to be {expected}"
+            ));
+        }
+        if paths == expected && side.is_none() {
+            side = Some(reached);
+        }
+    }
+    let side = side.ok_or(format!(
+        "Every component is joined by more than {expected} disjoint paths"
+    ))?;
+    if !is_connected_within(graph, &side, true) || !is_connected_within(graph, &side, false) {
+        return Err(format!(
+            "Cutting {expected} wires leaves more than two groups"
+        ));
+    }
+    let mut wires: Vec<(String, String)> = graph
+        .nodes()
+        .filter(|&node| side[node as usize])
+        .flat_map(|node| {
+            graph
+                .neighbors(node)
+                .iter()
+                .filter(|&&other| !side[other as usize])
+                .map(move |&other| (node, other))
+        })
+        .map(|(a, b)| {
+            let (a, b) = (names.label(a), names.label(b));
+            (a.min(b).to_string(), a.max(b).to_string())
+        })
         .collect();
-    eigen.sort_by(|x, y| x.0.partial_cmp(y.0).unwrap());
-    let (_, fielder) = eigen[1];
-    let set1 = fielder.into_iter().filter(|&x| *x > 0.0).count();
-    (N - set1) * set1
+    wires.sort();
+    let size1 = side.iter().filter(|&&reached| reached).count();
+    Ok(MinCut {
+        wires,
+        sizes: (size1, graph.len() - size1),
+    })
+}
+
+fn day25_p1(data: &str) -> usize {
+    let wiring = parse_wiring(data);
+    let cut = min_cut(&wiring, 3).unwrap_or_else(|err| panic!("No 3 wire cut: {err}"));
+    cut.sizes.0 * cut.sizes.1
 }
 
 pub fn run_day25_p1() -> usize {
     let filename = "data/day_25.txt";
     let data = read_to_string(filename).unwrap();
-    // sort of cheating here using a const for
-    // something I had to compute from the input
-    day25_p1::<1476>(&data)
+    day25_p1(&data)
 }
 
 fn main() {
+    if env::args().nth(1).as_deref() == Some("dot") {
+        let data = read_to_string("data/day_25.txt").unwrap();
+        print!("{}", parse_wiring(&data).to_dot());
+        return;
+    }
     let now = Instant::now();
     let sol = run_day25_p1();
     let elapsed = now.elapsed().as_millis();
@@ -83,7 +241,88 @@ mod test {
 
     #[test]
     fn test_day25_p1_example() {
-        assert_eq!(day25_p1::<15>(EXAMPLE), 54);
+        assert_eq!(day25_p1(EXAMPLE), 54);
+    }
+
+    #[test]
+    fn test_day25_min_cut() {
+        let wiring = parse_wiring(EXAMPLE);
+        assert_eq!(wiring.graph.arc_count(), 66);
+        let cut = min_cut(&wiring, 3).unwrap();
+        let wires: Vec<(&str, &str)> = cut
+            .wires
+            .iter()
+            .map(|(a, b)| (a.as_str(), b.as_str()))
+            .collect();
+        assert_eq!(wires, [("bvb", "cmg"), ("hfx", "pzl"), ("jqt", "nvd")]);
+        assert_eq!(cut.sizes.0 * cut.sizes.1, 54);
+        assert_eq!(cut.sizes.0 + cut.sizes.1, 15);
+        assert!(min_cut(&wiring, 2).is_err());
+        assert!(min_cut(&wiring, 4).is_err());
+    }
+
+    /// A cluster of `size` components, a ring with two pseudo-random chords
+    /// from every component
+    fn cluster(prefix: &str, size: usize, seed: u64) -> Vec<String> {
+        let mut seed = seed;
+        let mut next = |n: usize| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            (seed >> 33) as usize % n
+        };
+        (0..size)
+            .map(|ii| {
+                let mut chord = || (ii + 2 + next(size - 3)) % size;
+                let (chord1, chord2) = (chord(), chord());
+                format!(
+                    "{prefix}{ii}: {prefix}{} {prefix}{chord1} {prefix}{chord2}",
+                    (ii + 1) % size
+                )
+            })
+            .collect()
+    }
+
+    /// Two clusters of `sizes.0` and `sizes.1` components joined by three wires
+    fn two_clusters(sizes: (usize, usize)) -> String {
+        let mut lines = cluster("a", sizes.0, 42);
+        lines.extend(cluster("b", sizes.1, 43));
+        lines.push("a0: b0".to_string());
+        lines.push(format!("a{}: b{}", sizes.0 / 2, sizes.1 / 3));
+        lines.push(format!("a{}: b{}", sizes.0 - 1, sizes.1 - 1));
+        lines.join("\n")
+    }
+
+    #[test]
+    fn test_day25_min_cut_clusters() {
+        let wiring = parse_wiring(&two_clusters((300, 500)));
+        let cut = min_cut(&wiring, 3).unwrap();
+        assert_eq!(cut.sizes.0.min(cut.sizes.1), 300);
+        assert_eq!(cut.sizes.0 + cut.sizes.1, 800);
+        assert!(cut.wires.contains(&("a0".to_string(), "b0".to_string())));
+        assert_eq!(cut.wires.len(), 3);
+        // a component hanging off by two wires is a smaller cut
+        let wiring = parse_wiring(&format!("{}\nextra: a1 a2", two_clusters((30, 50))));
+        let err = min_cut(&wiring, 3).unwrap_err();
+        assert!(err.contains("extra has only 2 wires"));
+    }
+
+    #[test]
+    fn test_day25_min_cut_bottleneck() {
+        // a third cluster hanging off the first by two wires, so the smallest
+        // cut is inside the group that's three wires away from the other
+        let mut lines = vec![two_clusters((30, 50))];
+        lines.extend(cluster("c", 40, 44));
+        lines.push("c0: a5".to_string());
+        lines.push("c20: a17".to_string());
+        let wiring = parse_wiring(&lines.join("\n"));
+        let err = min_cut(&wiring, 3).unwrap_err();
+        assert!(err.contains("joined by only 2 disjoint paths"), "{err}");
+    }
+
+    #[test]
+    fn test_day25_dot() {
+        let dot = parse_wiring(EXAMPLE).to_dot().to_string();
+        assert!(dot.starts_with("graph \"wiring\" {\n    \"jqt\" -- \"rhn\";\n"));
+        assert_eq!(dot.matches(" -- ").count(), 33);
     }
 
     #[test]
@@ -0,0 +1,124 @@
+//! Writing graphs out in Graphviz DOT format, mostly for
+//! the days where the answer needed some data inspection.
+//! Render with e.g. `dot -Tsvg graph.dot > graph.svg`
+use std::fmt;
+
+#[derive(Debug, Clone)]
+struct Element {
+    ids: Vec<String>,
+    attrs: Vec<(String, String)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Graph {
+    name: String,
+    directed: bool,
+    nodes: Vec<Element>,
+    edges: Vec<Element>,
+}
+
+impl Graph {
+    pub fn directed(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            directed: true,
+            nodes: vec![],
+            edges: vec![],
+        }
+    }
+
+    pub fn undirected(name: &str) -> Self {
+        Self {
+            directed: false,
+            ..Self::directed(name)
+        }
+    }
+
+    /// Adds a node with the given attributes, e.g. `[("shape", "box")]`.
+    /// Nodes only need to be added explicitly if they have attributes.
+    pub fn node(&mut self, id: &str, attrs: &[(&str, &str)]) {
+        self.nodes.push(Element {
+            ids: vec![id.to_string()],
+            attrs: to_owned(attrs),
+        });
+    }
+
+    pub fn edge(&mut self, from: &str, to: &str, attrs: &[(&str, &str)]) {
+        self.edges.push(Element {
+            ids: vec![from.to_string(), to.to_string()],
+            attrs: to_owned(attrs),
+        });
+    }
+}
+
+fn to_owned(attrs: &[(&str, &str)]) -> Vec<(String, String)> {
+    attrs
+        .iter()
+        .map(|(key, val)| (key.to_string(), val.to_string()))
+        .collect()
+}
+
+/// Everything gets quoted so ids like `broadcaster` or `11A` don't need special handling
+fn quote(id: &str) -> String {
+    format!("\"{}\"", id.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.attrs.is_empty() {
+            let attrs: Vec<String> = self
+                .attrs
+                .iter()
+                .map(|(key, val)| format!("{key}={}", quote(val)))
+                .collect();
+            write!(f, " [{}]", attrs.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Graph {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (kind, arrow) = if self.directed {
+            ("digraph", " -> ")
+        } else {
+            ("graph", " -- ")
+        };
+        writeln!(f, "{kind} {} {{", quote(&self.name))?;
+        for node in &self.nodes {
+            writeln!(f, "    {}{node};", quote(&node.ids[0]))?;
+        }
+        for edge in &self.edges {
+            let ids: Vec<String> = edge.ids.iter().map(|id| quote(id)).collect();
+            writeln!(f, "    {}{edge};", ids.join(arrow))?;
+        }
+        writeln!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_dot_output() {
+        let mut graph = Graph::directed("circuit");
+        graph.node("a", &[("shape", "box")]);
+        graph.edge("a", "b \"quoted\"", &[]);
+        graph.edge("b \"quoted\"", "a", &[("label", "2")]);
+        assert_eq!(
+            graph.to_string(),
+            "digraph \"circuit\" {\n    \
+                \"a\" [shape=\"box\"];\n    \
+                \"a\" -> \"b \\\"quoted\\\"\";\n    \
+                \"b \\\"quoted\\\"\" -> \"a\" [label=\"2\"];\n\
+            }\n"
+        );
+        let mut graph = Graph::undirected("wires");
+        graph.edge("a", "b", &[]);
+        assert_eq!(
+            graph.to_string(),
+            "graph \"wires\" {\n    \"a\" -- \"b\";\n}\n"
+        );
+    }
+}
@@ -0,0 +1,341 @@
+//! Graphs where the nodes are named by strings in the input (day 8's `AAA`,
+//! day 20's modules, day 25's components) but are much nicer to work
+//! with as dense integer ids once parsing is done.
+use std::collections::HashMap;
+use std::ops::Range;
+
+/// Dense ids for string labels, handed out in the order they're first seen
+#[derive(Debug, Clone, Default)]
+pub struct Interner {
+    ids: HashMap<String, u32>,
+    labels: Vec<String>,
+}
+
+impl Interner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Id of this label, adding it if it's new
+    pub fn intern(&mut self, label: &str) -> u32 {
+        if let Some(&id) = self.ids.get(label) {
+            return id;
+        }
+        let id = self.labels.len() as u32;
+        self.ids.insert(label.to_string(), id);
+        self.labels.push(label.to_string());
+        id
+    }
+
+    pub fn id(&self, label: &str) -> Option<u32> {
+        self.ids.get(label).copied()
+    }
+
+    pub fn label(&self, id: u32) -> &str {
+        &self.labels[id as usize]
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn ids(&self) -> Range<u32> {
+        0..self.labels.len() as u32
+    }
+}
+
+/// A graph whose nodes are interned labels, with each node's outgoing
+/// edges stored as an `A`. That's a `Vec<u32>` for a general graph, but can
+/// be something more compact like `[u32; 2]` when every node has the same
+/// number of edges.
+#[derive(Debug, Clone, Default)]
+pub struct LabeledGraph<A = Vec<u32>> {
+    labels: Interner,
+    adjacency: Vec<A>,
+}
+
+impl<A: Default> LabeledGraph<A> {
+    pub fn new() -> Self {
+        Self {
+            labels: Interner::new(),
+            adjacency: vec![],
+        }
+    }
+
+    /// Id of the node with this label, adding it (with no edges) if it's new
+    pub fn intern(&mut self, label: &str) -> u32 {
+        let id = self.labels.intern(label);
+        if id as usize == self.adjacency.len() {
+            self.adjacency.push(A::default());
+        }
+        id
+    }
+}
+
+impl<A> LabeledGraph<A> {
+    pub fn id(&self, label: &str) -> Option<u32> {
+        self.labels.id(label)
+    }
+
+    pub fn label(&self, id: u32) -> &str {
+        self.labels.label(id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.labels.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.labels.is_empty()
+    }
+
+    pub fn ids(&self) -> Range<u32> {
+        self.labels.ids()
+    }
+
+    pub fn neighbors(&self, id: u32) -> &A {
+        &self.adjacency[id as usize]
+    }
+
+    pub fn neighbors_mut(&mut self, id: u32) -> &mut A {
+        &mut self.adjacency[id as usize]
+    }
+}
+
+impl LabeledGraph<Vec<u32>> {
+    /// Adds a directed edge, interning either label if needed
+    pub fn add_edge(&mut self, from: &str, to: &str) -> (u32, u32) {
+        let (from, to) = (self.intern(from), self.intern(to));
+        self.adjacency[from as usize].push(to);
+        (from, to)
+    }
+
+    /// Adds edges both ways
+    pub fn add_undirected_edge(&mut self, a: &str, b: &str) -> (u32, u32) {
+        let (a, b) = self.add_edge(a, b);
+        self.adjacency[b as usize].push(a);
+        (a, b)
+    }
+}
+
+/// Compressed sparse row adjacency for graphs too big to want a `Vec` per
+/// node: the neighbors of `node` are `targets[offsets[node]..offsets[node + 1]]`.
+/// Each slot in `targets` is a directed arc, and its index makes a handy key
+/// for per-arc data like flows kept in a plain `Vec`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CsrGraph {
+    offsets: Vec<usize>,
+    targets: Vec<u32>,
+    /// arc going the opposite way to each arc
+    reverse: Vec<usize>,
+}
+
+impl CsrGraph {
+    /// Builds an undirected graph on nodes `0..nodes`, with an arc each way
+    /// for every edge. Self-loops are left out, since they'd need a single
+    /// arc to be its own reverse.
+    pub fn undirected(nodes: usize, edges: &[(u32, u32)]) -> Self {
+        let edges: Vec<(u32, u32)> = edges.iter().copied().filter(|(a, b)| a != b).collect();
+        let mut offsets = vec![0; nodes + 1];
+        for &(a, b) in edges.iter() {
+            offsets[a as usize + 1] += 1;
+            offsets[b as usize + 1] += 1;
+        }
+        for node in 0..nodes {
+            offsets[node + 1] += offsets[node];
+        }
+        let mut next = offsets.clone();
+        let mut targets = vec![0; 2 * edges.len()];
+        let mut reverse = vec![0; 2 * edges.len()];
+        for &(a, b) in edges.iter() {
+            let (ab, ba) = (next[a as usize], next[b as usize]);
+            next[a as usize] += 1;
+            next[b as usize] += 1;
+            targets[ab] = b;
+            targets[ba] = a;
+            reverse[ab] = ba;
+            reverse[ba] = ab;
+        }
+        Self {
+            offsets,
+            targets,
+            reverse,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn nodes(&self) -> Range<u32> {
+        0..self.len() as u32
+    }
+
+    pub fn arc_count(&self) -> usize {
+        self.targets.len()
+    }
+
+    /// Ids of the arcs leaving `node`
+    pub fn arcs(&self, node: u32) -> Range<usize> {
+        self.offsets[node as usize]..self.offsets[node as usize + 1]
+    }
+
+    pub fn target(&self, arc: usize) -> u32 {
+        self.targets[arc]
+    }
+
+    pub fn reverse(&self, arc: usize) -> usize {
+        self.reverse[arc]
+    }
+
+    pub fn neighbors(&self, node: u32) -> &[u32] {
+        &self.targets[self.arcs(node)]
+    }
+}
+
+/// Strongly connected components of the graph on nodes `0..adjacency.len()`,
+/// where `adjacency[node]` lists the nodes its edges go to. Found with
+/// Tarjan's algorithm, kept iterative so long chains can't overflow the
+/// stack. Components come out in the order they finish, so every component
+/// is after all the ones it has edges into, and each one's nodes are sorted.
+pub fn strongly_connected_components<A: AsRef<[usize]>>(adjacency: &[A]) -> Vec<Vec<usize>> {
+    const UNSEEN: usize = usize::MAX;
+    let count = adjacency.len();
+    let mut index = vec![UNSEEN; count];
+    let mut lowlink = vec![0; count];
+    let mut on_stack = vec![false; count];
+    let mut stack = vec![];
+    let mut components = vec![];
+    let mut next_index = 0;
+    // the depth first path, with how many edges of each node have been tried
+    let mut path: Vec<(usize, usize)> = vec![];
+    for root in 0..count {
+        if index[root] != UNSEEN {
+            continue;
+        }
+        path.push((root, 0));
+        while let Some(&(node, edge)) = path.last() {
+            if edge == 0 && index[node] == UNSEEN {
+                index[node] = next_index;
+                lowlink[node] = next_index;
+                next_index += 1;
+                stack.push(node);
+                on_stack[node] = true;
+            }
+            if let Some(&next) = adjacency[node].as_ref().get(edge) {
+                path.last_mut().unwrap().1 += 1;
+                if index[next] == UNSEEN {
+                    path.push((next, 0));
+                } else if on_stack[next] {
+                    lowlink[node] = lowlink[node].min(index[next]);
+                }
+                continue;
+            }
+            path.pop();
+            if let Some(&(parent, _)) = path.last() {
+                lowlink[parent] = lowlink[parent].min(lowlink[node]);
+            }
+            if lowlink[node] == index[node] {
+                let pos = stack.iter().rposition(|&n| n == node).unwrap();
+                let mut component: Vec<usize> = stack.drain(pos..).collect();
+                for &member in &component {
+                    on_stack[member] = false;
+                }
+                component.sort_unstable();
+                components.push(component);
+            }
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_interner() {
+        let mut labels = Interner::new();
+        assert!(labels.is_empty());
+        assert_eq!(labels.intern("broadcaster"), 0);
+        assert_eq!(labels.intern("inv"), 1);
+        assert_eq!(labels.intern("broadcaster"), 0);
+        assert_eq!(labels.id("inv"), Some(1));
+        assert_eq!(labels.id("con"), None);
+        assert_eq!(labels.label(1), "inv");
+        assert_eq!(labels.ids(), 0..2);
+    }
+
+    #[test]
+    fn test_labeled_graph() {
+        let mut graph: LabeledGraph<[u32; 2]> = LabeledGraph::new();
+        let aaa = graph.intern("AAA");
+        let bbb = graph.intern("BBB");
+        assert_eq!(graph.intern("AAA"), aaa);
+        *graph.neighbors_mut(aaa) = [bbb, bbb];
+        *graph.neighbors_mut(bbb) = [aaa, bbb];
+        assert_eq!(graph.neighbors(bbb)[0], aaa);
+        assert_eq!(graph.label(bbb), "BBB");
+        assert_eq!(graph.id("CCC"), None);
+        assert_eq!(graph.ids(), 0..2);
+
+        let mut graph = LabeledGraph::new();
+        let (a, b) = graph.add_undirected_edge("a", "b");
+        graph.add_edge("a", "c");
+        assert_eq!(graph.neighbors(a), &vec![b, 2]);
+        assert_eq!(graph.neighbors(b), &vec![a]);
+        assert_eq!(graph.len(), 3);
+    }
+
+    #[test]
+    fn test_strongly_connected_components() {
+        // 0 -> 1 -> 2 -> 0 loop, feeding 3 <-> 4, and 5 on its own
+        let adjacency = vec![vec![1], vec![2], vec![0, 3], vec![4], vec![3], vec![5, 0]];
+        assert_eq!(
+            strongly_connected_components(&adjacency),
+            [vec![3, 4], vec![0, 1, 2], vec![5]]
+        );
+        let empty: Vec<Vec<usize>> = vec![];
+        assert!(strongly_connected_components(&empty).is_empty());
+
+        // a chain far longer than a recursive version could go
+        let chain: Vec<Vec<usize>> = (0..1_000_000)
+            .map(|ii| vec![ii + 1])
+            .chain([vec![0]])
+            .collect();
+        assert_eq!(strongly_connected_components(&chain).len(), 1);
+    }
+
+    #[test]
+    fn test_csr_graph() {
+        let graph = CsrGraph::undirected(4, &[(0, 1), (1, 2), (2, 0), (3, 1)]);
+        assert_eq!(graph.len(), 4);
+        assert_eq!(graph.arc_count(), 8);
+        assert_eq!(graph.neighbors(0), &[1, 2]);
+        assert_eq!(graph.neighbors(1), &[0, 2, 3]);
+        assert_eq!(graph.neighbors(3), &[1]);
+        for node in graph.nodes() {
+            for arc in graph.arcs(node) {
+                let back = graph.reverse(arc);
+                assert_eq!(graph.target(back), node);
+                assert_eq!(graph.reverse(back), arc);
+            }
+        }
+        assert!(CsrGraph::undirected(0, &[]).is_empty());
+
+        let graph = CsrGraph::undirected(2, &[(0, 0), (0, 1), (1, 1)]);
+        assert_eq!(graph.arc_count(), 2);
+        assert_eq!(graph.neighbors(0), &[1]);
+        assert_eq!(graph.neighbors(1), &[0]);
+        assert_eq!(graph.reverse(graph.reverse(0)), 0);
+        assert_eq!(graph.target(graph.reverse(0)), 0);
+    }
+}
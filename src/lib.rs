@@ -0,0 +1,5 @@
+//! Helpers shared between the daily solutions in `src/bin`
+pub mod dot;
+pub mod graph;
+pub mod number_theory;
+pub mod rational;
@@ -0,0 +1,216 @@
+//! Number theory helpers for lining up cycles: extended GCD, modular
+//! inverses and the Chinese Remainder Theorem (CRT) for moduli that
+//! aren't necessarily coprime.
+//!
+//! The answers these days produce get close to the limits of `i128` once a
+//! few large cycles are combined, so the `i128` versions check every step
+//! for overflow, and [`crt_big`] is there for when that isn't enough.
+use num::{BigInt, Integer, One, Signed, Zero};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CrtError {
+    /// The congruences contradict each other, so there is no solution
+    Inconsistent,
+    /// The solution exists but doesn't fit in an `i128`
+    Overflow,
+    /// A modulus of zero isn't a congruence at all
+    ZeroModulus,
+}
+
+/// Returns `(g, x, y)` with `g = gcd(a, b) >= 0` and `a * x + b * y = g`
+pub fn extended_gcd(a: i128, b: i128) -> (i128, i128, i128) {
+    let (mut old_r, mut r) = (a, b);
+    let (mut old_x, mut x) = (1, 0);
+    let (mut old_y, mut y) = (0, 1);
+    while r != 0 {
+        let quot = old_r / r;
+        (old_r, r) = (r, old_r - quot * r);
+        (old_x, x) = (x, old_x - quot * x);
+        (old_y, y) = (y, old_y - quot * y);
+    }
+    if old_r < 0 {
+        (-old_r, -old_x, -old_y)
+    } else {
+        (old_r, old_x, old_y)
+    }
+}
+
+/// The `x` in `0..|m|` with `a * x = 1 (mod m)`, if `a` and `m` are coprime
+pub fn mod_inverse(a: i128, m: i128) -> Option<i128> {
+    let m = m.checked_abs()?;
+    if m == 0 {
+        return None;
+    }
+    let (g, x, _) = extended_gcd(a.rem_euclid(m), m);
+    (g == 1).then(|| x.rem_euclid(m))
+}
+
+/// `a * b mod m` for `a, b` in `0..m`, without overflowing even when `a * b` would
+fn mul_mod(a: i128, b: i128, m: i128) -> i128 {
+    if let Some(product) = a.checked_mul(b) {
+        return product % m;
+    }
+    // double and add, every intermediate stays below 2 * m < 2^128
+    let (a, mut b, m) = (a as u128, b as u128, m as u128);
+    let (mut result, mut a) = (0, a);
+    while b > 0 {
+        if b & 1 == 1 {
+            result = (result + a) % m;
+        }
+        a = (a + a) % m;
+        b >>= 1;
+    }
+    result as i128
+}
+
+pub fn checked_lcm(a: i128, b: i128) -> Option<i128> {
+    if a == 0 || b == 0 {
+        return Some(0);
+    }
+    (a / a.gcd(&b)).checked_mul(b)?.checked_abs()
+}
+
+/// Solves `x = a_i (mod n_i)` for every `(a_i, n_i)`, returning `(x, n)` where `n`
+/// is the lcm of the moduli, `x` is in `0..n`, and every solution is `x` plus a
+/// multiple of `n`. Negative moduli are treated as their absolute value.
+pub fn crt(congruences: &[(i128, i128)]) -> Result<(i128, i128), CrtError> {
+    congruences.iter().try_fold((0, 1), |(a1, n1), &(a2, n2)| {
+        let n2 = n2.checked_abs().ok_or(CrtError::Overflow)?;
+        if n2 == 0 {
+            return Err(CrtError::ZeroModulus);
+        }
+        let a2 = a2.rem_euclid(n2);
+        let (g, p, _) = extended_gcd(n1, n2);
+        // (a2 - a1) can't overflow since both are non-negative
+        let diff = a2 - a1;
+        if diff % g != 0 {
+            return Err(CrtError::Inconsistent);
+        }
+        // x = a1 + n1 * k where n1 * k = a2 - a1 (mod n2), i.e.
+        // k = (a2 - a1) / g * p (mod n2 / g)
+        let n2g = n2 / g;
+        let k = mul_mod((diff / g).rem_euclid(n2g), p.rem_euclid(n2g), n2g);
+        let modulus = n1.checked_mul(n2g).ok_or(CrtError::Overflow)?;
+        // n1 * k < n1 * n2g = modulus, and a1 < n1, so neither can overflow
+        Ok(((a1 + n1 * k) % modulus, modulus))
+    })
+}
+
+/// [`crt`] with arbitrary precision, for when the lcm of the moduli is too big
+/// for an `i128`. Returns `None` if the congruences are inconsistent.
+pub fn crt_big(congruences: &[(i128, i128)]) -> Option<(BigInt, BigInt)> {
+    congruences
+        .iter()
+        .try_fold((BigInt::zero(), BigInt::one()), |(a1, n1), &(a2, n2)| {
+            let n2 = BigInt::from(n2).abs();
+            if n2.is_zero() {
+                return None;
+            }
+            let a2 = BigInt::from(a2).mod_floor(&n2);
+            let egcd = n1.extended_gcd(&n2);
+            let diff = a2 - &a1;
+            if !(&diff % &egcd.gcd).is_zero() {
+                return None;
+            }
+            let n2g = &n2 / &egcd.gcd;
+            let k = (diff / &egcd.gcd * egcd.x).mod_floor(&n2g);
+            let modulus = &n1 * &n2g;
+            Some(((a1 + n1 * k).mod_floor(&modulus), modulus))
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Small deterministic pseudo-random numbers for the fuzz tests
+    struct Lcg(u64);
+
+    impl Lcg {
+        fn next(&mut self, below: i128) -> i128 {
+            self.0 = self
+                .0
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (self.0 >> 33) as i128 % below
+        }
+    }
+
+    #[test]
+    fn test_extended_gcd() {
+        assert_eq!(extended_gcd(240, 46), (2, -9, 47));
+        assert_eq!(extended_gcd(-4, 6).0, 2);
+        assert_eq!(extended_gcd(0, -5), (5, 0, -1));
+        let mut rng = Lcg(7);
+        for _ in 0..1000 {
+            let (a, b) = (rng.next(2000) - 1000, rng.next(2000) - 1000);
+            let (g, x, y) = extended_gcd(a, b);
+            assert_eq!(a * x + b * y, g);
+            assert_eq!(g, a.gcd(&b));
+        }
+    }
+
+    #[test]
+    fn test_mod_inverse() {
+        assert_eq!(mod_inverse(3, 11), Some(4));
+        assert_eq!(mod_inverse(-3, 11), Some(7));
+        assert_eq!(mod_inverse(4, 8), None);
+        assert_eq!(mod_inverse(4, 0), None);
+    }
+
+    #[test]
+    fn test_crt() {
+        assert_eq!(crt(&[(2, 3), (3, 5), (2, 7)]), Ok((23, 105)));
+        assert_eq!(crt(&[(1, 2), (1, 3), (1, 4), (1, 5), (1, 6)]), Ok((1, 60)));
+        assert_eq!(crt(&[(1, 4), (2, 6)]), Err(CrtError::Inconsistent));
+        assert_eq!(crt(&[(1, 4), (2, 0)]), Err(CrtError::ZeroModulus));
+        assert_eq!(crt(&[(-1, -4)]), Ok((3, 4)));
+        assert_eq!(crt(&[]), Ok((0, 1)));
+    }
+
+    #[test]
+    fn test_crt_overflow() {
+        let big = [(1, i128::MAX / 3), (2, i128::MAX / 5 - 2)];
+        assert_eq!(crt(&big), Err(CrtError::Overflow));
+        let (x, n) = crt_big(&big).unwrap();
+        assert_eq!(
+            &n,
+            &(BigInt::from(i128::MAX / 3) * BigInt::from(i128::MAX / 5 - 2))
+        );
+        assert_eq!(x.mod_floor(&BigInt::from(big[0].1)), BigInt::from(1));
+        assert_eq!(x.mod_floor(&BigInt::from(big[1].1)), BigInt::from(2));
+        // the lcm fits, but the intermediate products in a naive implementation don't
+        let near = [(12345, (1 << 62) + 1), (678, (1 << 62) - 1)];
+        let (x, n) = crt(&near).unwrap();
+        assert_eq!(x % near[0].1, near[0].0);
+        assert_eq!(x % near[1].1, near[1].0);
+        assert_eq!(n, ((1 << 62) + 1) * ((1 << 62) - 1));
+        assert_eq!(mul_mod(i128::MAX - 1, i128::MAX - 2, i128::MAX), 2);
+    }
+
+    #[test]
+    fn test_crt_fuzz() {
+        let mut rng = Lcg(2023);
+        for _ in 0..2000 {
+            let count = 1 + rng.next(4) as usize;
+            let congruences: Vec<(i128, i128)> = (0..count)
+                .map(|_| (rng.next(100) - 50, rng.next(24) + 1))
+                .collect();
+            let lcm = congruences
+                .iter()
+                .fold(1, |acc, &(_, n)| checked_lcm(acc, n).unwrap());
+            let brute =
+                (0..lcm).find(|x| congruences.iter().all(|&(a, n)| (x - a).rem_euclid(n) == 0));
+            match brute {
+                Some(x) => {
+                    assert_eq!(crt(&congruences), Ok((x, lcm)));
+                    assert_eq!(crt_big(&congruences), Some((x.into(), lcm.into())));
+                }
+                None => {
+                    assert_eq!(crt(&congruences), Err(CrtError::Inconsistent));
+                    assert_eq!(crt_big(&congruences), None);
+                }
+            }
+        }
+    }
+}
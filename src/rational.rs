@@ -0,0 +1,257 @@
+//! Exact fractions of `i128`s. Always stored in lowest terms with a positive
+//! denominator, so equal values have equal representations.
+//!
+//! The operators panic on overflow, like integer arithmetic does in debug
+//! builds. Use the `checked_*` methods where overflow is a real possibility.
+use num::{BigInt, Integer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numer: i128,
+    denom: i128,
+}
+
+impl Rational {
+    pub const ZERO: Self = Self { numer: 0, denom: 1 };
+    pub const ONE: Self = Self { numer: 1, denom: 1 };
+
+    /// Panics if `denom` is zero or the normalized value overflows
+    pub fn new(numer: i128, denom: i128) -> Self {
+        assert!(denom != 0, "Rational with a zero denominator");
+        Self::checked_new(numer, denom).expect("Rational overflow")
+    }
+
+    /// None if `denom` is zero or the normalized value overflows
+    /// (only possible when negating `i128::MIN`)
+    pub fn checked_new(numer: i128, denom: i128) -> Option<Self> {
+        if denom == 0 {
+            return None;
+        }
+        let gcd = numer.gcd(&denom);
+        let (numer, denom) = (numer / gcd, denom / gcd);
+        if denom < 0 {
+            Some(Self {
+                numer: numer.checked_neg()?,
+                denom: denom.checked_neg()?,
+            })
+        } else {
+            Some(Self { numer, denom })
+        }
+    }
+
+    pub fn numer(&self) -> i128 {
+        self.numer
+    }
+
+    pub fn denom(&self) -> i128 {
+        self.denom
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denom == 1
+    }
+
+    pub fn signum(&self) -> i128 {
+        self.numer.signum()
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        self.numer as f64 / self.denom as f64
+    }
+
+    pub fn checked_add(self, other: Self) -> Option<Self> {
+        // scale by the lcm of the denominators rather than their product
+        let gcd = self.denom.gcd(&other.denom);
+        let (self_scale, other_scale) = (other.denom / gcd, self.denom / gcd);
+        let numer = self
+            .numer
+            .checked_mul(self_scale)?
+            .checked_add(other.numer.checked_mul(other_scale)?)?;
+        Self::checked_new(numer, self.denom.checked_mul(self_scale)?)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Option<Self> {
+        self.checked_add(other.checked_neg()?)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Option<Self> {
+        // cancel common factors first to keep the products small
+        let gcd1 = self.numer.gcd(&other.denom).max(1);
+        let gcd2 = other.numer.gcd(&self.denom).max(1);
+        let numer = (self.numer / gcd1).checked_mul(other.numer / gcd2)?;
+        let denom = (self.denom / gcd2).checked_mul(other.denom / gcd1)?;
+        Self::checked_new(numer, denom)
+    }
+
+    /// None on overflow or division by zero
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        self.checked_mul(other.checked_recip()?)
+    }
+
+    pub fn checked_neg(self) -> Option<Self> {
+        Some(Self {
+            numer: self.numer.checked_neg()?,
+            denom: self.denom,
+        })
+    }
+
+    pub fn checked_recip(self) -> Option<Self> {
+        Self::checked_new(self.denom, self.numer)
+    }
+}
+
+impl From<i128> for Rational {
+    fn from(value: i128) -> Self {
+        Self {
+            numer: value,
+            denom: 1,
+        }
+    }
+}
+
+impl TryFrom<Rational> for i128 {
+    type Error = &'static str;
+
+    fn try_from(value: Rational) -> Result<Self, Self::Error> {
+        if value.is_integer() {
+            Ok(value.numer)
+        } else {
+            Err("Rational is not an integer")
+        }
+    }
+}
+
+impl Add for Rational {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        self.checked_add(other).expect("Rational overflow")
+    }
+}
+
+impl Sub for Rational {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self.checked_sub(other).expect("Rational overflow")
+    }
+}
+
+impl Mul for Rational {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        self.checked_mul(other).expect("Rational overflow")
+    }
+}
+
+impl Div for Rational {
+    type Output = Self;
+
+    fn div(self, other: Self) -> Self {
+        assert!(other.numer != 0, "Rational division by zero");
+        self.checked_div(other).expect("Rational overflow")
+    }
+}
+
+impl Neg for Rational {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        self.checked_neg().expect("Rational overflow")
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // denominators are positive, so cross multiplying keeps the order
+        match (
+            self.numer.checked_mul(other.denom),
+            other.numer.checked_mul(self.denom),
+        ) {
+            (Some(lhs), Some(rhs)) => lhs.cmp(&rhs),
+            _ => {
+                let lhs = BigInt::from(self.numer) * BigInt::from(other.denom);
+                let rhs = BigInt::from(other.numer) * BigInt::from(self.denom);
+                lhs.cmp(&rhs)
+            }
+        }
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numer)
+        } else {
+            write!(f, "{}/{}", self.numer, self.denom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_normalization() {
+        assert_eq!(Rational::new(4, -6), Rational::new(-2, 3));
+        assert_eq!(Rational::new(-4, -6).denom(), 3);
+        assert_eq!(Rational::new(0, -5), Rational::ZERO);
+        assert_eq!(Rational::checked_new(1, 0), None);
+        assert_eq!(Rational::checked_new(i128::MIN, -1), None);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let (a, b) = (Rational::new(1, 6), Rational::new(3, 4));
+        assert_eq!(a + b, Rational::new(11, 12));
+        assert_eq!(a - b, Rational::new(-7, 12));
+        assert_eq!(a * b, Rational::new(1, 8));
+        assert_eq!(a / b, Rational::new(2, 9));
+        assert_eq!(-a, Rational::new(-1, 6));
+        assert_eq!(a.checked_div(Rational::ZERO), None);
+        assert_eq!(i128::try_from(b * Rational::from(8)), Ok(6));
+        assert!(i128::try_from(b).is_err());
+    }
+
+    #[test]
+    fn test_overflow() {
+        let big = Rational::from(i128::MAX);
+        assert_eq!(big.checked_add(Rational::ONE), None);
+        assert_eq!(big.checked_mul(Rational::from(2)), None);
+        // cancelling before multiplying avoids overflowing here
+        let half = Rational::new(1, 2);
+        assert_eq!(
+            big.checked_mul(Rational::new(2, i128::MAX)),
+            Some(Rational::from(2))
+        );
+        assert_eq!((big * half) * Rational::from(2), big);
+    }
+
+    #[test]
+    fn test_ordering_and_display() {
+        let mut values = [
+            Rational::new(1, 2),
+            Rational::new(-3, 2),
+            Rational::from(2),
+            Rational::new(1, 3),
+        ];
+        values.sort();
+        let shown: Vec<String> = values.iter().map(|x| x.to_string()).collect();
+        assert_eq!(shown, vec!["-3/2", "1/3", "1/2", "2"]);
+        // too big to cross multiply in an i128
+        let (a, b) = (Rational::new(i128::MAX, 3), Rational::new(i128::MAX - 1, 3));
+        assert!(a > b);
+        assert!(Rational::new(-i128::MAX, 7) < Rational::new(i128::MAX, 11));
+    }
+}